@@ -2,10 +2,11 @@
 mod tests;
 pub mod types;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use derive_more::{Display, From};
+use serde::Serialize;
 
 use binding_macro::{cycles, genesis, service, write};
 use protocol::traits::{ExecutorParams, ServiceSDK, StoreMap};
@@ -13,43 +14,80 @@ use protocol::types::{Address, Hash, ServiceContext};
 use protocol::{ProtocolError, ProtocolErrorKind, ProtocolResult};
 
 use crate::types::{
-    ApproveEvent, ApprovePayload, Asset, AssetBalance, CreateAssetPayload, GetAllowancePayload,
-    GetAllowanceResponse, GetAssetPayload, GetBalancePayload, GetBalanceResponse,
-    InitGenesisPayload, TransferEvent, TransferFromEvent, TransferFromPayload, TransferPayload,
+    AcceptIssuerEvent, AcceptIssuerPayload, ApproveEvent, ApprovePayload, Asset, AssetBalance,
+    AssetIntegrityReport, BlacklistPayload, ComputeAssetIdPayload, CreateAssetPayload,
+    GetAllowancePayload, GetAllowanceResponse, GetAllowancesPayload, GetAllowancesResponse,
+    GetAssetPayload, GetAssetsPayload, GetAssetsResponse, GetBalancePayload, GetBalanceResponse,
+    GetBalancesPayload, GetTotalOutstandingAllowancePayload, GetTotalOutstandingAllowanceResponse,
+    InitGenesisPayload, PreflightTransferResponse, ProposeIssuerEvent, ProposeIssuerPayload,
+    ReservedRecipientPayload, SetAssetPausedEvent, SetAssetPausedPayload, SetBlacklistedEvent,
+    SetFrozenEvent, SetFrozenPayload, SetReservedRecipientEvent, TransferEvent, TransferFromEvent,
+    TransferFromPayload, TransferPayload, UpdateAssetMetaEvent, UpdateAssetMetaPayload,
+    VerifyAssetIntegrityPayload,
 };
 
+const MAX_MEMO_LEN: usize = 256;
+const MAX_NAME_LEN: usize = 64;
+const MAX_SYMBOL_LEN: usize = 16;
+
 pub struct AssetService<SDK> {
-    sdk:    SDK,
-    assets: Box<dyn StoreMap<Hash, Asset>>,
+    sdk:       SDK,
+    assets:    Box<dyn StoreMap<Hash, Asset>>,
+    reserved:  Box<dyn StoreMap<Hash, bool>>,
+    frozen:    Box<dyn StoreMap<Hash, bool>>,
+    blacklist: Box<dyn StoreMap<Hash, bool>>,
 }
 
 #[service]
 impl<SDK: ServiceSDK> AssetService<SDK> {
     pub fn new(mut sdk: SDK) -> ProtocolResult<Self> {
         let assets: Box<dyn StoreMap<Hash, Asset>> = sdk.alloc_or_recover_map("assets")?;
+        let reserved: Box<dyn StoreMap<Hash, bool>> =
+            sdk.alloc_or_recover_map("reserved_recipients")?;
+        let frozen: Box<dyn StoreMap<Hash, bool>> = sdk.alloc_or_recover_map("frozen_balances")?;
+        let blacklist: Box<dyn StoreMap<Hash, bool>> = sdk.alloc_or_recover_map("blacklist")?;
 
-        Ok(Self { sdk, assets })
+        Ok(Self {
+            sdk,
+            assets,
+            reserved,
+            frozen,
+            blacklist,
+        })
     }
 
     #[genesis]
-    fn init_genesis(&mut self, payload: InitGenesisPayload) -> ProtocolResult<()> {
-        let asset = Asset {
-            id:     payload.id,
-            name:   payload.name,
-            symbol: payload.symbol,
-            supply: payload.supply,
-            issuer: payload.issuer.clone(),
-        };
+    fn init_genesis(&mut self, payloads: Vec<InitGenesisPayload>) -> ProtocolResult<()> {
+        for payload in payloads {
+            if self.assets.contains(&payload.id)? {
+                return Err(ServiceError::Exists { id: payload.id }.into());
+            }
+            verify_memo(&payload.memo)?;
 
-        self.assets.insert(asset.id.clone(), asset.clone())?;
+            let asset = Asset {
+                id:             payload.id,
+                name:           payload.name,
+                symbol:         payload.symbol,
+                supply:         payload.supply,
+                issuer:         payload.issuer.clone(),
+                paused:         false,
+                pending_issuer: None,
+                memo:           payload.memo,
+                holder_count:   Some(if payload.supply > 0 { 1 } else { 0 }),
+            };
 
-        let asset_balance = AssetBalance {
-            value:     payload.supply,
-            allowance: BTreeMap::new(),
-        };
+            self.assets.insert(asset.id.clone(), asset.clone())?;
 
-        self.sdk
-            .set_account_value(&asset.issuer, asset.id, asset_balance)
+            let asset_balance = AssetBalance {
+                value:     payload.supply,
+                allowance: BTreeMap::new(),
+            };
+
+            self.sdk
+                .set_account_value(&asset.issuer, asset.id, asset_balance)?;
+        }
+
+        Ok(())
     }
 
     #[cycles(100_00)]
@@ -59,6 +97,91 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         Ok(asset)
     }
 
+    #[cycles(100_00)]
+    #[read]
+    fn get_assets(
+        &self,
+        ctx: ServiceContext,
+        payload: GetAssetsPayload,
+    ) -> ProtocolResult<GetAssetsResponse> {
+        let total = u64::from(self.assets.len()?);
+
+        let mut assets = Vec::new();
+        for (_, asset) in self
+            .assets
+            .iter()
+            .skip(payload.offset as usize)
+            .take(payload.limit as usize)
+        {
+            // Every asset returned costs 10_00 cycles
+            ctx.sub_cycles(10_00)?;
+            assets.push(asset);
+        }
+
+        Ok(GetAssetsResponse { assets, total })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn compute_asset_id(
+        &self,
+        ctx: ServiceContext,
+        payload: ComputeAssetIdPayload,
+    ) -> ProtocolResult<Hash> {
+        derive_asset_id(&payload.asset, &payload.caller)
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn verify_asset_integrity(
+        &self,
+        ctx: ServiceContext,
+        payload: VerifyAssetIntegrityPayload,
+    ) -> ProtocolResult<AssetIntegrityReport> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        // Duplicated holders would be counted twice
+        let holders: BTreeSet<Address> = payload.holders.into_iter().collect();
+        // Every holder queried costs 100_00 cycles
+        ctx.sub_cycles(holders.len() as u64 * 100_00)?;
+
+        let mut balance_sum: u128 = 0;
+        let mut holders_with_balance: u64 = 0;
+        for holder in holders.iter() {
+            let balance = self
+                .sdk
+                .get_account_value::<_, AssetBalance>(holder, &payload.asset_id)?
+                .map_or(0, |b| b.value);
+            if balance > 0 {
+                holders_with_balance += 1;
+            }
+            balance_sum = balance_sum
+                .checked_add(balance)
+                .ok_or(ServiceError::U128Overflow)?;
+        }
+
+        // Supply is fixed at creation, so the holders must own all of it
+        let asset = self.assets.get(&payload.asset_id)?;
+        let consistent = balance_sum == asset.supply
+            && asset
+                .holder_count
+                .map_or(true, |count| count == holders_with_balance);
+
+        Ok(AssetIntegrityReport {
+            asset_id: payload.asset_id,
+            supply: asset.supply,
+            balance_sum,
+            holder_count: asset.holder_count,
+            holders_with_balance,
+            consistent,
+        })
+    }
+
     #[cycles(100_00)]
     #[read]
     fn get_balance(
@@ -88,6 +211,51 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         })
     }
 
+    #[cycles(100_00)]
+    #[read]
+    fn get_balances(
+        &self,
+        ctx: ServiceContext,
+        payload: GetBalancesPayload,
+    ) -> ProtocolResult<Vec<GetBalanceResponse>> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        if let Some(user) = payload
+            .users
+            .iter()
+            .find(|user| user.as_bytes().iter().all(|b| *b == 0))
+        {
+            return Err(ServiceError::InvalidAddress {
+                address: user.clone(),
+            }
+            .into());
+        }
+
+        // Every user queried costs 100_00 cycles
+        ctx.sub_cycles(payload.users.len() as u64 * 100_00)?;
+
+        let mut balances = Vec::with_capacity(payload.users.len());
+        for user in payload.users {
+            let balance = self
+                .sdk
+                .get_account_value::<_, AssetBalance>(&user, &payload.asset_id)?
+                .map_or(0, |b| b.value);
+
+            balances.push(GetBalanceResponse {
+                asset_id: payload.asset_id.clone(),
+                user,
+                balance,
+            });
+        }
+
+        Ok(balances)
+    }
+
     #[cycles(100_00)]
     #[read]
     fn get_allowance(
@@ -125,6 +293,98 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         }
     }
 
+    #[cycles(100_00)]
+    #[read]
+    fn get_allowances(
+        &self,
+        ctx: ServiceContext,
+        payload: GetAllowancesPayload,
+    ) -> ProtocolResult<GetAllowancesResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        let allowances: Vec<(Address, u128)> = self
+            .sdk
+            .get_account_value::<_, AssetBalance>(&payload.grantor, &payload.asset_id)?
+            .map(|b| b.allowance.into_iter().collect())
+            .unwrap_or_default();
+
+        // Every allowance returned costs 100_00 cycles
+        ctx.sub_cycles(allowances.len() as u64 * 100_00)?;
+
+        Ok(GetAllowancesResponse {
+            asset_id: payload.asset_id,
+            grantor: payload.grantor,
+            allowances,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn get_total_outstanding_allowance(
+        &self,
+        ctx: ServiceContext,
+        payload: GetTotalOutstandingAllowancePayload,
+    ) -> ProtocolResult<GetTotalOutstandingAllowanceResponse> {
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        // Every grantor scanned costs 100_00 cycles
+        ctx.sub_cycles(payload.grantors.len() as u64 * 100_00)?;
+
+        let mut total: u128 = 0;
+        for grantor in payload.grantors.iter() {
+            let opt_asset_balance: Option<AssetBalance> =
+                self.sdk.get_account_value(grantor, &payload.asset_id)?;
+
+            if let Some(asset_balance) = opt_asset_balance {
+                for allowance in asset_balance.allowance.values() {
+                    total = total
+                        .checked_add(*allowance)
+                        .ok_or(ServiceError::U128Overflow)?;
+                }
+            }
+        }
+
+        Ok(GetTotalOutstandingAllowanceResponse {
+            asset_id: payload.asset_id,
+            total,
+        })
+    }
+
+    #[cycles(100_00)]
+    #[read]
+    fn preflight_transfer(
+        &self,
+        ctx: ServiceContext,
+        payload: TransferPayload,
+    ) -> ProtocolResult<PreflightTransferResponse> {
+        let caller = ctx.get_caller();
+        let mut violation = expired_violation(&ctx, payload.valid_until_height);
+        if violation.is_none() {
+            violation =
+                self.transfer_violation(&caller, &caller, &payload.to, &payload.asset_id)?;
+        }
+        if violation.is_none() {
+            violation =
+                self.balance_violation(&caller, &payload.to, &payload.asset_id, payload.value)?;
+        }
+        let failing_check = violation.map(|err| err.check_name(&caller).to_owned());
+
+        Ok(PreflightTransferResponse {
+            ok: failing_check.is_none(),
+            failing_check,
+        })
+    }
+
     #[cycles(210_00)]
     #[write]
     fn create_asset(
@@ -133,19 +393,24 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         payload: CreateAssetPayload,
     ) -> ProtocolResult<Asset> {
         let caller = ctx.get_caller();
-        let payload_str = serde_json::to_string(&payload).map_err(ServiceError::JsonParse)?;
-
-        let id = Hash::digest(Bytes::from(payload_str + &caller.as_hex()));
+        verify_name(&payload.name, MAX_NAME_LEN)?;
+        verify_name(&payload.symbol, MAX_SYMBOL_LEN)?;
+        verify_memo(&payload.memo)?;
+        let id = derive_asset_id(&payload, &caller)?;
 
         if self.assets.contains(&id)? {
             return Err(ServiceError::Exists { id }.into());
         }
         let asset = Asset {
-            id:     id.clone(),
-            name:   payload.name,
-            symbol: payload.symbol,
-            supply: payload.supply,
-            issuer: caller,
+            id:             id.clone(),
+            name:           payload.name,
+            symbol:         payload.symbol,
+            supply:         payload.supply,
+            issuer:         caller,
+            paused:         false,
+            pending_issuer: None,
+            memo:           payload.memo,
+            holder_count:   Some(if payload.supply > 0 { 1 } else { 0 }),
         };
         self.assets.insert(id, asset.clone())?;
 
@@ -171,8 +436,11 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         let value = payload.value;
         let to = payload.to;
 
-        if !self.assets.contains(&asset_id)? {
-            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        if let Some(err) = expired_violation(&ctx, payload.valid_until_height) {
+            return Err(err.into());
+        }
+        if let Some(err) = self.transfer_violation(&caller, &caller, &to, &asset_id)? {
+            return Err(err.into());
         }
 
         self._transfer(caller.clone(), to.clone(), asset_id.clone(), value)?;
@@ -229,6 +497,39 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         ctx.emit_event(event_str)
     }
 
+    #[cycles(210_00)]
+    #[write]
+    fn increase_allowance(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ApprovePayload,
+    ) -> ProtocolResult<()> {
+        let delta = payload.value;
+        self.adjust_allowance(ctx, payload, |allowance| {
+            allowance
+                .checked_add(delta)
+                .ok_or(ServiceError::U128Overflow)
+        })
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn decrease_allowance(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ApprovePayload,
+    ) -> ProtocolResult<()> {
+        let delta = payload.value;
+        self.adjust_allowance(ctx, payload, |allowance| {
+            allowance
+                .checked_sub(delta)
+                .ok_or(ServiceError::AllowanceUnderflow {
+                    expect: delta,
+                    real:   allowance,
+                })
+        })
+    }
+
     #[cycles(210_00)]
     #[write]
     fn transfer_from(
@@ -242,8 +543,8 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         let asset_id = payload.asset_id;
         let value = payload.value;
 
-        if !self.assets.contains(&asset_id)? {
-            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        if let Some(err) = self.transfer_violation(&caller, &sender, &recipient, &asset_id)? {
+            return Err(err.into());
         }
 
         let mut sender_asset_balance: AssetBalance = self
@@ -258,7 +559,7 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
             .entry(caller.clone())
             .or_insert(0);
         if *sender_allowance < value {
-            return Err(ServiceError::LackOfBalance {
+            return Err(ServiceError::AllowanceNotEnough {
                 expect: value,
                 real:   *sender_allowance,
             }
@@ -286,15 +587,391 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
         ctx.emit_event(event_str)
     }
 
+    #[cycles(210_00)]
+    #[write]
+    fn set_asset_paused(
+        &mut self,
+        ctx: ServiceContext,
+        payload: SetAssetPausedPayload,
+    ) -> ProtocolResult<()> {
+        self.update_as_issuer(&ctx, &payload.asset_id, |service, mut asset| {
+            asset.paused = payload.paused;
+            service.assets.insert(asset.id.clone(), asset)?;
+
+            Ok(SetAssetPausedEvent {
+                asset_id: payload.asset_id.clone(),
+                paused:   payload.paused,
+            })
+        })
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn set_frozen(&mut self, ctx: ServiceContext, payload: SetFrozenPayload) -> ProtocolResult<()> {
+        self.update_as_issuer(&ctx, &payload.asset_id, |service, _| {
+            let key = asset_address_key(&payload.asset_id, &payload.address);
+            set_flag(service.frozen.as_mut(), key, payload.frozen)?;
+
+            Ok(SetFrozenEvent {
+                asset_id: payload.asset_id.clone(),
+                address:  payload.address.clone(),
+                frozen:   payload.frozen,
+            })
+        })
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn add_to_blacklist(
+        &mut self,
+        ctx: ServiceContext,
+        payload: BlacklistPayload,
+    ) -> ProtocolResult<()> {
+        self.set_blacklisted(ctx, payload, true)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn remove_from_blacklist(
+        &mut self,
+        ctx: ServiceContext,
+        payload: BlacklistPayload,
+    ) -> ProtocolResult<()> {
+        self.set_blacklisted(ctx, payload, false)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn update_asset_meta(
+        &mut self,
+        ctx: ServiceContext,
+        payload: UpdateAssetMetaPayload,
+    ) -> ProtocolResult<()> {
+        if payload.name.is_none() && payload.symbol.is_none() {
+            return Err(ServiceError::MeaningLessValue.into());
+        }
+        if let Some(name) = &payload.name {
+            verify_name(name, MAX_NAME_LEN)?;
+        }
+        if let Some(symbol) = &payload.symbol {
+            verify_name(symbol, MAX_SYMBOL_LEN)?;
+        }
+
+        let asset_id = payload.asset_id.clone();
+        self.update_as_issuer(&ctx, &asset_id, |service, mut asset| {
+            if let Some(name) = payload.name {
+                asset.name = name;
+            }
+            if let Some(symbol) = payload.symbol {
+                asset.symbol = symbol;
+            }
+            service.assets.insert(asset.id.clone(), asset.clone())?;
+
+            Ok(UpdateAssetMetaEvent {
+                asset_id: asset.id,
+                name:     asset.name,
+                symbol:   asset.symbol,
+            })
+        })
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn propose_issuer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ProposeIssuerPayload,
+    ) -> ProtocolResult<()> {
+        let mut asset = self.get_asset_require_issuer(&ctx.get_caller(), &payload.asset_id)?;
+        asset.pending_issuer = Some(payload.new_issuer.clone());
+        self.assets.insert(asset.id.clone(), asset.clone())?;
+
+        let event = ProposeIssuerEvent {
+            asset_id:       payload.asset_id,
+            issuer:         asset.issuer,
+            pending_issuer: payload.new_issuer,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn accept_issuer(
+        &mut self,
+        ctx: ServiceContext,
+        payload: AcceptIssuerPayload,
+    ) -> ProtocolResult<()> {
+        let caller = ctx.get_caller();
+
+        if !self.assets.contains(&payload.asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: payload.asset_id,
+            }
+            .into());
+        }
+
+        let mut asset = self.assets.get(&payload.asset_id)?;
+        if asset.pending_issuer.as_ref() != Some(&caller) {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        let old_issuer = std::mem::replace(&mut asset.issuer, caller.clone());
+        asset.pending_issuer = None;
+        self.assets.insert(asset.id.clone(), asset)?;
+
+        let event = AcceptIssuerEvent {
+            asset_id: payload.asset_id,
+            old_issuer,
+            new_issuer: caller,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn add_reserved_recipient(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ReservedRecipientPayload,
+    ) -> ProtocolResult<()> {
+        self.set_reserved_recipient(ctx, payload, true)
+    }
+
+    #[cycles(210_00)]
+    #[write]
+    fn remove_reserved_recipient(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ReservedRecipientPayload,
+    ) -> ProtocolResult<()> {
+        self.set_reserved_recipient(ctx, payload, false)
+    }
+
+    fn set_reserved_recipient(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ReservedRecipientPayload,
+        reserved: bool,
+    ) -> ProtocolResult<()> {
+        self.update_as_issuer(&ctx, &payload.asset_id, |service, _| {
+            let key = asset_address_key(&payload.asset_id, &payload.address);
+            set_flag(service.reserved.as_mut(), key, reserved)?;
+
+            Ok(SetReservedRecipientEvent {
+                asset_id: payload.asset_id.clone(),
+                address: payload.address.clone(),
+                reserved,
+            })
+        })
+    }
+
+    fn adjust_allowance<F>(
+        &mut self,
+        ctx: ServiceContext,
+        payload: ApprovePayload,
+        adjust: F,
+    ) -> ProtocolResult<()>
+    where
+        F: FnOnce(u128) -> Result<u128, ServiceError>,
+    {
+        let caller = ctx.get_caller();
+        let asset_id = payload.asset_id;
+        let to = payload.to;
+
+        if caller == to {
+            return Err(ServiceError::ApproveToYourself.into());
+        }
+
+        if !self.assets.contains(&asset_id)? {
+            return Err(ServiceError::NotFoundAsset { id: asset_id }.into());
+        }
+
+        let mut caller_asset_balance: AssetBalance = self
+            .sdk
+            .get_account_value(&caller, &asset_id)?
+            .unwrap_or(AssetBalance {
+                value:     0,
+                allowance: BTreeMap::new(),
+            });
+        let allowance = caller_asset_balance
+            .allowance
+            .entry(to.clone())
+            .or_insert(0);
+        let value = adjust(*allowance)?;
+        *allowance = value;
+
+        self.sdk
+            .set_account_value(&caller, asset_id.clone(), caller_asset_balance)?;
+
+        let event = ApproveEvent {
+            asset_id,
+            grantor: caller,
+            grantee: to,
+            value,
+        };
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    fn set_blacklisted(
+        &mut self,
+        ctx: ServiceContext,
+        payload: BlacklistPayload,
+        blacklisted: bool,
+    ) -> ProtocolResult<()> {
+        self.update_as_issuer(&ctx, &payload.asset_id, |service, _| {
+            let key = asset_address_key(&payload.asset_id, &payload.address);
+            set_flag(service.blacklist.as_mut(), key, blacklisted)?;
+
+            Ok(SetBlacklistedEvent {
+                asset_id: payload.asset_id.clone(),
+                address: payload.address.clone(),
+                blacklisted,
+            })
+        })
+    }
+
+    // Runs an issuer only update on the asset and emits the event it returns
+    fn update_as_issuer<E, F>(
+        &mut self,
+        ctx: &ServiceContext,
+        asset_id: &Hash,
+        update: F,
+    ) -> ProtocolResult<()>
+    where
+        E: Serialize,
+        F: FnOnce(&mut Self, Asset) -> ProtocolResult<E>,
+    {
+        let asset = self.get_asset_require_issuer(&ctx.get_caller(), asset_id)?;
+        let event = update(self, asset)?;
+
+        let event_str = serde_json::to_string(&event).map_err(ServiceError::JsonParse)?;
+        ctx.emit_event(event_str)
+    }
+
+    fn get_asset_require_issuer(&self, caller: &Address, asset_id: &Hash) -> ProtocolResult<Asset> {
+        if !self.assets.contains(asset_id)? {
+            return Err(ServiceError::NotFoundAsset {
+                id: asset_id.clone(),
+            }
+            .into());
+        }
+
+        let asset = self.assets.get(asset_id)?;
+        if &asset.issuer != caller {
+            return Err(ServiceError::NonAuthorized.into());
+        }
+
+        Ok(asset)
+    }
+
+    // Rules shared by transfer, transfer_from and preflight_transfer, returns
+    // the error of the first broken one
+    fn transfer_violation(
+        &self,
+        caller: &Address,
+        sender: &Address,
+        recipient: &Address,
+        asset_id: &Hash,
+    ) -> ProtocolResult<Option<ServiceError>> {
+        if !self.assets.contains(asset_id)? {
+            return Ok(Some(ServiceError::NotFoundAsset {
+                id: asset_id.clone(),
+            }));
+        }
+        if self.assets.get(asset_id)?.paused {
+            return Ok(Some(ServiceError::AssetPaused {
+                id: asset_id.clone(),
+            }));
+        }
+        // Frozen holders can still receive, but can't move their balance
+        if self.frozen.contains(&asset_address_key(asset_id, sender))? {
+            return Ok(Some(ServiceError::BalanceFrozen {
+                address: sender.clone(),
+            }));
+        }
+        // Blacklisted addresses can neither send nor receive, even from the issuer
+        for address in &[sender, recipient] {
+            if self
+                .blacklist
+                .contains(&asset_address_key(asset_id, address))?
+            {
+                return Ok(Some(ServiceError::Blacklisted {
+                    address: (*address).clone(),
+                }));
+            }
+        }
+        if self.is_recipient_reserved(caller, recipient, asset_id)? {
+            return Ok(Some(ServiceError::RecipientReserved {
+                address: recipient.clone(),
+            }));
+        }
+
+        Ok(None)
+    }
+
+    fn balance_violation(
+        &self,
+        sender: &Address,
+        recipient: &Address,
+        asset_id: &Hash,
+        value: u128,
+    ) -> ProtocolResult<Option<ServiceError>> {
+        if sender == recipient {
+            return Ok(Some(ServiceError::RecipientIsSender));
+        }
+
+        let sender_balance = self
+            .sdk
+            .get_account_value::<_, AssetBalance>(sender, asset_id)?
+            .map_or(0, |b| b.value);
+        if sender_balance < value {
+            return Ok(Some(ServiceError::LackOfBalance {
+                expect: value,
+                real:   sender_balance,
+            }));
+        }
+
+        let recipient_balance = self
+            .sdk
+            .get_account_value::<_, AssetBalance>(recipient, asset_id)?
+            .map_or(0, |b| b.value);
+        if recipient_balance.checked_add(value).is_none() {
+            return Ok(Some(ServiceError::U128Overflow));
+        }
+
+        Ok(None)
+    }
+
+    // Reserved addresses only accept transfers initiated by the asset issuer
+    fn is_recipient_reserved(
+        &self,
+        caller: &Address,
+        recipient: &Address,
+        asset_id: &Hash,
+    ) -> ProtocolResult<bool> {
+        if !self
+            .reserved
+            .contains(&asset_address_key(asset_id, recipient))?
+        {
+            return Ok(false);
+        }
+
+        let asset = self.assets.get(asset_id)?;
+        Ok(&asset.issuer != caller)
+    }
+
     fn _transfer(
         &mut self,
         sender: Address,
         recipient: Address,
         asset_id: Hash,
-        value: u64,
+        value: u128,
     ) -> ProtocolResult<()> {
-        if sender == recipient {
-            return Err(ServiceError::RecipientIsSender.into());
+        if let Some(err) = self.balance_violation(&sender, &recipient, &asset_id, value)? {
+            return Err(err.into());
         }
 
         let mut sender_asset_balance: AssetBalance = self
@@ -306,14 +983,6 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
             });
         let sender_balance = sender_asset_balance.value;
 
-        if sender_balance < value {
-            return Err(ServiceError::LackOfBalance {
-                expect: value,
-                real:   sender_balance,
-            }
-            .into());
-        }
-
         let mut to_asset_balance: AssetBalance = self
             .sdk
             .get_account_value(&recipient, &asset_id)?
@@ -322,9 +991,10 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
                 allowance: BTreeMap::new(),
             });
 
+        let recipient_balance = to_asset_balance.value;
         let (v, overflow) = to_asset_balance.value.overflowing_add(value);
         if overflow {
-            return Err(ServiceError::U64Overflow.into());
+            return Err(ServiceError::U128Overflow.into());
         }
         to_asset_balance.value = v;
 
@@ -333,16 +1003,92 @@ impl<SDK: ServiceSDK> AssetService<SDK> {
 
         let (v, overflow) = sender_balance.overflowing_sub(value);
         if overflow {
-            return Err(ServiceError::U64Overflow.into());
+            return Err(ServiceError::U128Overflow.into());
         }
         sender_asset_balance.value = v;
         self.sdk
-            .set_account_value(&sender, asset_id, sender_asset_balance)?;
+            .set_account_value(&sender, asset_id.clone(), sender_asset_balance)?;
+
+        // Count stays the same when the recipient joins as the sender leaves
+        let recipient_joined = value > 0 && recipient_balance == 0;
+        let sender_left = value > 0 && v == 0;
+        if recipient_joined != sender_left {
+            let mut asset = self.assets.get(&asset_id)?;
+            if let Some(count) = asset.holder_count {
+                let count = if recipient_joined {
+                    count + 1
+                } else {
+                    count
+                        .checked_sub(1)
+                        .ok_or_else(|| ServiceError::HolderCountUnderflow {
+                            id: asset_id.clone(),
+                        })?
+                };
+                asset.holder_count = Some(count);
+                self.assets.insert(asset_id, asset)?;
+            }
+        }
 
         Ok(())
     }
 }
 
+fn derive_asset_id(payload: &CreateAssetPayload, caller: &Address) -> ProtocolResult<Hash> {
+    let payload_str = serde_json::to_string(payload).map_err(ServiceError::JsonParse)?;
+    Ok(Hash::digest(Bytes::from(payload_str + &caller.as_hex())))
+}
+
+fn expired_violation(
+    ctx: &ServiceContext,
+    valid_until_height: Option<u64>,
+) -> Option<ServiceError> {
+    let current_height = ctx.get_current_height();
+    match valid_until_height {
+        Some(valid_until_height) if current_height > valid_until_height => {
+            Some(ServiceError::Expired {
+                valid_until_height,
+                current_height,
+            })
+        }
+        _ => None,
+    }
+}
+
+// Names and symbols must be non-empty and within the length limit
+fn verify_name(name: &str, max_len: usize) -> Result<(), ServiceError> {
+    let len = name.chars().count();
+    if len == 0 || len > max_len {
+        return Err(ServiceError::MeaningLessValue);
+    }
+
+    Ok(())
+}
+
+fn verify_memo(memo: &str) -> Result<(), ServiceError> {
+    let len = memo.chars().count();
+    if len > MAX_MEMO_LEN {
+        return Err(ServiceError::MemoTooLong { len });
+    }
+
+    Ok(())
+}
+
+fn set_flag(flags: &mut dyn StoreMap<Hash, bool>, key: Hash, flag: bool) -> ProtocolResult<()> {
+    if flag {
+        flags.insert(key, true)?;
+    } else if flags.contains(&key)? {
+        flags.remove(&key)?;
+    }
+
+    Ok(())
+}
+
+fn asset_address_key(asset_id: &Hash, address: &Address) -> Hash {
+    let mut key = BytesMut::from(asset_id.as_bytes().as_ref());
+    key.extend(address.as_bytes());
+    Hash::digest(key.freeze())
+}
+
 #[derive(Debug, Display, From)]
 pub enum ServiceError {
     #[display(fmt = "Parsing payload to json failed {:?}", _0)]
@@ -360,15 +1106,95 @@ pub enum ServiceError {
 
     #[display(fmt = "Not found asset, expect {:?} real {:?}", expect, real)]
     LackOfBalance {
-        expect: u64,
-        real:   u64,
+        expect: u128,
+        real:   u128,
+    },
+
+    U128Overflow,
+
+    #[display(fmt = "Allowance not enough, expect {:?} real {:?}", expect, real)]
+    AllowanceNotEnough {
+        expect: u128,
+        real:   u128,
     },
 
-    U64Overflow,
+    #[display(fmt = "Allowance underflow, expect {:?} real {:?}", expect, real)]
+    AllowanceUnderflow {
+        expect: u128,
+        real:   u128,
+    },
 
     RecipientIsSender,
 
     ApproveToYourself,
+
+    NonAuthorized,
+
+    MeaningLessValue,
+
+    #[display(fmt = "Asset {:?} is paused", id)]
+    AssetPaused {
+        id: Hash,
+    },
+
+    #[display(fmt = "Balance of {:?} is frozen", address)]
+    BalanceFrozen {
+        address: Address,
+    },
+
+    #[display(fmt = "Memo too long, length {}, max {}", len, MAX_MEMO_LEN)]
+    MemoTooLong {
+        len: usize,
+    },
+
+    #[display(
+        fmt = "Expired, valid until height {} current height {}",
+        valid_until_height,
+        current_height
+    )]
+    Expired {
+        valid_until_height: u64,
+        current_height:     u64,
+    },
+
+    #[display(fmt = "Invalid address {:?}", address)]
+    InvalidAddress {
+        address: Address,
+    },
+
+    #[display(fmt = "Address {:?} is blacklisted", address)]
+    Blacklisted {
+        address: Address,
+    },
+
+    #[display(fmt = "Holder count of asset {:?} underflow", id)]
+    HolderCountUnderflow {
+        id: Hash,
+    },
+
+    #[display(fmt = "Recipient {:?} is reserved", address)]
+    RecipientReserved {
+        address: Address,
+    },
+}
+
+impl ServiceError {
+    // Check name reported by preflight_transfer
+    fn check_name(&self, sender: &Address) -> &'static str {
+        match self {
+            ServiceError::Expired { .. } => "expired",
+            ServiceError::NotFoundAsset { .. } => "asset_not_found",
+            ServiceError::AssetPaused { .. } => "asset_paused",
+            ServiceError::BalanceFrozen { .. } => "sender_frozen",
+            ServiceError::Blacklisted { address } if address == sender => "sender_blacklisted",
+            ServiceError::Blacklisted { .. } => "recipient_blacklisted",
+            ServiceError::RecipientReserved { .. } => "recipient_reserved",
+            ServiceError::RecipientIsSender => "recipient_is_sender",
+            ServiceError::LackOfBalance { .. } => "insufficient_balance",
+            ServiceError::U128Overflow => "recipient_overflow",
+            _ => "unknown",
+        }
+    }
 }
 
 impl std::error::Error for ServiceError {}
@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -7,18 +8,64 @@ use cita_trie::MemoryDB;
 
 use framework::binding::sdk::{DefalutServiceSDK, DefaultChainQuerier};
 use framework::binding::state::{GeneralServiceState, MPTTrie};
-use protocol::traits::{NoopDispatcher, Storage};
+use protocol::fixed_codec::FixedCodec;
+use protocol::traits::{NoopDispatcher, ServiceSDK, Storage};
 use protocol::types::{
     Address, Block, Hash, Proof, Receipt, ServiceContext, ServiceContextParams, SignedTransaction,
 };
 use protocol::{types::Bytes, ProtocolResult};
 
 use crate::types::{
-    ApprovePayload, CreateAssetPayload, GetAllowancePayload, GetAssetPayload, GetBalancePayload,
-    TransferFromPayload, TransferPayload,
+    AcceptIssuerPayload, ApproveEvent, ApprovePayload, Asset, AssetBalance, BlacklistPayload,
+    ComputeAssetIdPayload, CreateAssetPayload, GetAllowancePayload, GetAllowancesPayload,
+    GetAssetPayload, GetAssetsPayload, GetBalancePayload, GetBalancesPayload,
+    GetTotalOutstandingAllowancePayload, InitGenesisPayload, ProposeIssuerPayload,
+    ReservedRecipientPayload, SetAssetPausedPayload, SetFrozenPayload, TransferEvent,
+    TransferFromPayload, TransferPayload, UpdateAssetMetaPayload, VerifyAssetIntegrityPayload,
 };
 use crate::AssetService;
 
+#[test]
+fn test_init_genesis_multiple_assets() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let mt = InitGenesisPayload {
+        id:     Hash::digest(Bytes::from("MT")),
+        name:   "Muta Token".to_owned(),
+        symbol: "MT".to_owned(),
+        supply: 1024,
+        issuer: issuer.clone(),
+        memo:   String::new(),
+    };
+    let ht = InitGenesisPayload {
+        id:     Hash::digest(Bytes::from("HT")),
+        name:   "Huobi Token".to_owned(),
+        symbol: "HT".to_owned(),
+        supply: 2048,
+        issuer: issuer.clone(),
+        memo:   String::new(),
+    };
+    service.init_genesis(vec![mt.clone(), ht.clone()]).unwrap();
+
+    for payload in vec![mt.clone(), ht] {
+        let balance_res = service
+            .get_balance(context.clone(), GetBalancePayload {
+                asset_id: payload.id,
+                user:     issuer.clone(),
+            })
+            .unwrap();
+        assert_eq!(balance_res.balance, payload.supply);
+    }
+
+    // Duplicate ids should be rejected
+    let mut service = new_asset_service();
+    assert!(service.init_genesis(vec![mt.clone(), mt]).is_err());
+}
+
 #[test]
 fn test_create_asset() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
@@ -34,6 +81,7 @@ fn test_create_asset() {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            memo: String::new(),
         })
         .unwrap();
 
@@ -54,6 +102,204 @@ fn test_create_asset() {
     assert_eq!(balance_res.asset_id, asset.id);
 }
 
+#[test]
+fn test_compute_asset_id() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let payload = CreateAssetPayload {
+        name:   "test".to_owned(),
+        symbol: "test".to_owned(),
+        supply: 1024,
+        memo:   String::new(),
+    };
+    let id = service
+        .compute_asset_id(context.clone(), ComputeAssetIdPayload {
+            caller: caller.clone(),
+            asset:  payload.clone(),
+        })
+        .unwrap();
+
+    // Computing doesn't create anything
+    assert!(service
+        .get_asset(context.clone(), GetAssetPayload { id: id.clone() })
+        .is_err());
+
+    let asset = service
+        .create_asset(context.clone(), payload.clone())
+        .unwrap();
+    assert_eq!(asset.id, id);
+
+    // Id depends on the caller
+    let other = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let other_id = service
+        .compute_asset_id(context, ComputeAssetIdPayload {
+            caller: other,
+            asset:  payload,
+        })
+        .unwrap();
+    assert_ne!(other_id, id);
+}
+
+#[test]
+fn test_create_asset_with_memo() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            memo:   "backed by huobi".to_owned(),
+        })
+        .unwrap();
+
+    let new_asset = service
+        .get_asset(context.clone(), GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(new_asset.memo, "backed by huobi");
+
+    // Memo is capped at 256 chars
+    assert!(service
+        .create_asset(context, CreateAssetPayload {
+            name:   "test2".to_owned(),
+            symbol: "test2".to_owned(),
+            supply: 1024,
+            memo:   "m".repeat(257),
+        })
+        .is_err());
+}
+
+#[test]
+fn test_holder_count() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+    assert_eq!(asset.holder_count, Some(1));
+
+    let user_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let holder_count = |service: &AssetService<_>| {
+        service
+            .get_asset(context.clone(), GetAssetPayload {
+                id: asset.id.clone(),
+            })
+            .unwrap()
+            .holder_count
+    };
+    let transfer = |service: &mut AssetService<_>, from: &Address, to: &Address, value: u128| {
+        service
+            .transfer(mock_context(cycles_limit, from.clone()), TransferPayload {
+                asset_id: asset.id.clone(),
+                to: to.clone(),
+                value,
+                valid_until_height: None,
+            })
+            .unwrap()
+    };
+
+    transfer(&mut service, &caller, &user_a, 24);
+    assert_eq!(holder_count(&service), Some(2));
+
+    // Zero value transfer doesn't add holder
+    transfer(&mut service, &caller, &user_b, 0);
+    assert_eq!(holder_count(&service), Some(2));
+
+    // Sender leaves while recipient joins
+    transfer(&mut service, &user_a, &user_b, 24);
+    assert_eq!(holder_count(&service), Some(2));
+
+    transfer(&mut service, &user_b, &caller, 24);
+    assert_eq!(holder_count(&service), Some(1));
+}
+
+#[test]
+fn test_get_assets() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller);
+
+    let mut service = new_asset_service();
+
+    for i in 0..5 {
+        service
+            .create_asset(context.clone(), CreateAssetPayload {
+                name:   format!("test{}", i),
+                symbol: format!("test{}", i),
+                supply: 1024,
+                memo:   String::new(),
+            })
+            .unwrap();
+    }
+
+    let page = service
+        .get_assets(context.clone(), GetAssetsPayload {
+            offset: 0,
+            limit:  3,
+        })
+        .unwrap();
+    assert_eq!(page.total, 5);
+    assert_eq!(page.assets.len(), 3);
+
+    let next_page = service
+        .get_assets(context.clone(), GetAssetsPayload {
+            offset: 3,
+            limit:  3,
+        })
+        .unwrap();
+    assert_eq!(next_page.total, 5);
+    assert_eq!(next_page.assets.len(), 2);
+    assert!(next_page
+        .assets
+        .iter()
+        .all(|asset| !page.assets.contains(asset)));
+
+    let empty_page = service
+        .get_assets(context.clone(), GetAssetsPayload {
+            offset: 5,
+            limit:  3,
+        })
+        .unwrap();
+    assert!(empty_page.assets.is_empty());
+
+    // Only returned assets are charged, whatever the offset
+    let cycles_before = context.get_cycles_used();
+    service
+        .get_assets(context.clone(), GetAssetsPayload {
+            offset: 0,
+            limit:  2,
+        })
+        .unwrap();
+    let first_page_cycles = context.get_cycles_used() - cycles_before;
+
+    let cycles_before = context.get_cycles_used();
+    service
+        .get_assets(context.clone(), GetAssetsPayload {
+            offset: 3,
+            limit:  2,
+        })
+        .unwrap();
+    assert_eq!(context.get_cycles_used() - cycles_before, first_page_cycles);
+}
+
 #[test]
 fn test_transfer() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
@@ -69,15 +315,17 @@ fn test_transfer() {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            memo: String::new(),
         })
         .unwrap();
 
     let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     service
         .transfer(context.clone(), TransferPayload {
-            asset_id: asset.id.clone(),
-            to:       to_address.clone(),
-            value:    1024,
+            asset_id:           asset.id.clone(),
+            to:                 to_address.clone(),
+            value:              1024,
+            valid_until_height: None,
         })
         .unwrap();
 
@@ -100,101 +348,211 @@ fn test_transfer() {
 }
 
 #[test]
-fn test_approve() {
+fn test_get_balances() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
     let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     let context = mock_context(cycles_limit, caller.clone());
 
     let mut service = new_asset_service();
 
-    let supply = 1024 * 1024;
     let asset = service
         .create_asset(context.clone(), CreateAssetPayload {
-            name: "test".to_owned(),
+            name:   "test".to_owned(),
             symbol: "test".to_owned(),
-            supply,
+            supply: 1024 * 1024,
+            memo:   String::new(),
         })
         .unwrap();
 
-    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let funded = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let empty = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     service
-        .approve(context.clone(), ApprovePayload {
-            asset_id: asset.id.clone(),
-            to:       to_address.clone(),
-            value:    1024,
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 funded.clone(),
+            value:              1024,
+            valid_until_height: None,
         })
         .unwrap();
 
-    let allowance_res = service
-        .get_allowance(context, GetAllowancePayload {
+    let balances = service
+        .get_balances(context.clone(), GetBalancesPayload {
             asset_id: asset.id.clone(),
-            grantor:  caller,
-            grantee:  to_address.clone(),
+            users:    vec![caller.clone(), funded.clone(), empty.clone()],
         })
         .unwrap();
-    assert_eq!(allowance_res.asset_id, asset.id);
-    assert_eq!(allowance_res.grantee, to_address);
-    assert_eq!(allowance_res.value, 1024);
+    assert_eq!(balances.len(), 3);
+    assert_eq!(balances[0].user, caller);
+    assert_eq!(balances[0].balance, 1024 * 1024 - 1024);
+    assert_eq!(balances[1].user, funded);
+    assert_eq!(balances[1].balance, 1024);
+    assert_eq!(balances[2].user, empty);
+    assert_eq!(balances[2].balance, 0);
+
+    let zero = Address::from_hex("0x0000000000000000000000000000000000000000").unwrap();
+    let err = service
+        .get_balances(context, GetBalancesPayload {
+            asset_id: asset.id,
+            users:    vec![funded, zero],
+        })
+        .unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("invalid address"));
 }
 
 #[test]
-fn test_transfer_from() {
+fn test_u128_supply() {
     let cycles_limit = 1024 * 1024 * 1024; // 1073741824
     let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
     let context = mock_context(cycles_limit, caller.clone());
 
     let mut service = new_asset_service();
 
-    let supply = 1024 * 1024;
+    let supply = u128::from(u64::max_value()) * 1024;
     let asset = service
         .create_asset(context.clone(), CreateAssetPayload {
             name: "test".to_owned(),
             symbol: "test".to_owned(),
             supply,
+            memo: String::new(),
         })
         .unwrap();
 
     let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let value = u128::from(u64::max_value()) + 1;
     service
-        .approve(context.clone(), ApprovePayload {
+        .transfer(context.clone(), TransferPayload {
             asset_id: asset.id.clone(),
-            to:       to_address.clone(),
-            value:    1024,
+            to: to_address.clone(),
+            value,
+            valid_until_height: None,
         })
         .unwrap();
 
-    let to_context = mock_context(cycles_limit, to_address.clone());
+    let balances = service
+        .get_balances(context.clone(), GetBalancesPayload {
+            asset_id: asset.id.clone(),
+            users:    vec![caller, to_address],
+        })
+        .unwrap();
+    assert_eq!(balances[0].balance, supply - value);
+    assert_eq!(balances[1].balance, value);
 
-    service
-        .transfer_from(to_context.clone(), TransferFromPayload {
-            asset_id:  asset.id.clone(),
-            sender:    caller.clone(),
-            recipient: to_address.clone(),
-            value:     24,
+    let stored = service
+        .get_asset(context, GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(stored.supply, supply);
+}
+
+#[test]
+fn test_decode_u64_encoded_balance() {
+    let grantee = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    // Balance encoded before the u128 migration
+    let mut stream = rlp::RlpStream::new_list(2);
+    stream.append(&1024u64);
+    stream.begin_list(1);
+    stream.begin_list(2).append(&grantee).append(&24u64);
+
+    let balance = AssetBalance::decode_fixed(Bytes::from(stream.out())).unwrap();
+    assert_eq!(balance.value, 1024);
+    assert_eq!(balance.allowance.get(&grantee), Some(&24));
+
+    let bytes = balance.encode_fixed().unwrap();
+    let decoded = AssetBalance::decode_fixed(bytes).unwrap();
+    assert_eq!(decoded.value, 1024);
+    assert_eq!(decoded.allowance.get(&grantee), Some(&24));
+}
+
+#[test]
+fn test_decode_asset_without_holder_count() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            memo:   String::new(),
         })
         .unwrap();
 
-    let allowance_res = service
-        .get_allowance(context.clone(), GetAllowancePayload {
-            asset_id: asset.id.clone(),
-            grantor:  caller.clone(),
-            grantee:  to_address.clone(),
+    // Asset encoded before holder tracking
+    let mut stream = rlp::RlpStream::new_list(8);
+    stream
+        .append(&asset.id)
+        .append(&asset.name)
+        .append(&asset.symbol)
+        .append(&1024u64)
+        .append(&asset.issuer)
+        .append(&asset.paused);
+    stream.append_list::<Address, Address>(&[]);
+    stream.append(&asset.memo);
+
+    let legacy = Asset::decode_fixed(Bytes::from(stream.out())).unwrap();
+    assert_eq!(legacy.holder_count, None);
+    service.assets.insert(asset.id.clone(), legacy).unwrap();
+
+    // Unknown count stays unknown instead of being counted from zero
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 to_address,
+            value:              1024,
+            valid_until_height: None,
         })
         .unwrap();
-    assert_eq!(allowance_res.asset_id, asset.id.clone());
-    assert_eq!(allowance_res.grantee, to_address.clone());
-    assert_eq!(allowance_res.value, 1000);
 
-    let balance_res = service
-        .get_balance(context, GetBalancePayload {
-            asset_id: asset.id.clone(),
-            user:     caller,
+    let stored = service
+        .get_asset(context, GetAssetPayload { id: asset.id })
+        .unwrap();
+    assert_eq!(stored.holder_count, None);
+}
+
+#[test]
+fn test_transfer_valid_until_height() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context, CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            memo:   String::new(),
         })
         .unwrap();
-    assert_eq!(balance_res.balance, supply - 24);
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let payload = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 to_address.clone(),
+        value:              24,
+        valid_until_height: Some(10),
+    };
+
+    let expired_context = mock_context_at_height(cycles_limit, caller.clone(), 11);
+    assert!(service
+        .transfer(expired_context.clone(), payload.clone())
+        .is_err());
+    let res = service
+        .preflight_transfer(expired_context, payload.clone())
+        .unwrap();
+    assert_eq!(res.failing_check, Some("expired".to_owned()));
+
+    // Deadline height itself is still valid
+    let context = mock_context_at_height(cycles_limit, caller, 10);
+    service.transfer(context.clone(), payload).unwrap();
 
     let balance_res = service
-        .get_balance(to_context, GetBalancePayload {
+        .get_balance(context, GetBalancePayload {
             asset_id: asset.id,
             user:     to_address,
         })
@@ -202,27 +560,1066 @@ fn test_transfer_from() {
     assert_eq!(balance_res.balance, 24);
 }
 
-fn new_asset_service() -> AssetService<
-    DefalutServiceSDK<
-        GeneralServiceState<MemoryDB>,
-        DefaultChainQuerier<MockStorage>,
-        NoopDispatcher,
-    >,
-> {
-    let chain_db = DefaultChainQuerier::new(Arc::new(MockStorage {}));
-    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
-    let state = GeneralServiceState::new(trie);
+#[test]
+fn test_transfer_event() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
 
-    let sdk = DefalutServiceSDK::new(
-        Rc::new(RefCell::new(state)),
-        Rc::new(chain_db),
-        NoopDispatcher {},
-    );
+    let mut service = new_asset_service();
 
-    AssetService::new(sdk).unwrap()
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 to_address.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+
+    let events = context.get_events();
+    let event: TransferEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.asset_id, asset.id);
+    assert_eq!(event.from, caller);
+    assert_eq!(event.to, to_address);
+    assert_eq!(event.value, 1024);
 }
 
-fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
+#[test]
+fn test_transfer_to_self() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    // Rejected even if the value exceeds the balance
+    for value in &[24, 1024 * 1024] {
+        assert!(service
+            .transfer(context.clone(), TransferPayload {
+                asset_id:           asset.id.clone(),
+                to:                 caller.clone(),
+                value:              *value,
+                valid_until_height: None,
+            })
+            .is_err());
+    }
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1024);
+}
+
+#[test]
+fn test_approve() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            memo: String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context, GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller,
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.asset_id, asset.id);
+    assert_eq!(allowance_res.grantee, to_address);
+    assert_eq!(allowance_res.value, 1024);
+}
+
+#[test]
+fn test_increase_and_decrease_allowance() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let payload = |value: u128| ApprovePayload {
+        asset_id: asset.id.clone(),
+        to: to_address.clone(),
+        value,
+    };
+
+    service
+        .increase_allowance(context.clone(), payload(1024))
+        .unwrap();
+    service
+        .increase_allowance(context.clone(), payload(1024))
+        .unwrap();
+    service
+        .decrease_allowance(context.clone(), payload(24))
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller.clone(),
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.value, 2024);
+
+    // Event carries the resulting allowance
+    let events = context.get_events();
+    let event: ApproveEvent = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(event.value, 2024);
+
+    assert!(service
+        .decrease_allowance(context.clone(), payload(2025))
+        .is_err());
+    assert!(service
+        .increase_allowance(context.clone(), payload(u128::max_value()))
+        .is_err());
+
+    let self_payload = ApprovePayload {
+        asset_id: asset.id.clone(),
+        to:       caller,
+        value:    1,
+    };
+    assert!(service.increase_allowance(context, self_payload).is_err());
+}
+
+#[test]
+fn test_transfer_from() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let supply = 1024 * 1024;
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name: "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply,
+            memo: String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    let to_context = mock_context(cycles_limit, to_address.clone());
+
+    service
+        .transfer_from(to_context.clone(), TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller.clone(),
+            recipient: to_address.clone(),
+            value:     24,
+        })
+        .unwrap();
+
+    let allowance_res = service
+        .get_allowance(context.clone(), GetAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller.clone(),
+            grantee:  to_address.clone(),
+        })
+        .unwrap();
+    assert_eq!(allowance_res.asset_id, asset.id.clone());
+    assert_eq!(allowance_res.grantee, to_address.clone());
+    assert_eq!(allowance_res.value, 1000);
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, supply - 24);
+
+    let balance_res = service
+        .get_balance(to_context, GetBalancePayload {
+            asset_id: asset.id,
+            user:     to_address,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 24);
+}
+
+#[test]
+fn test_transfer_from_allowance_not_enough() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       to_address.clone(),
+            value:    24,
+        })
+        .unwrap();
+
+    // Sender has enough balance, but allowance is too low
+    let to_context = mock_context(cycles_limit, to_address.clone());
+    let err = service
+        .transfer_from(to_context, TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    caller.clone(),
+            recipient: to_address.clone(),
+            value:     1024,
+        })
+        .unwrap_err();
+    assert!(
+        err.to_string().to_lowercase().contains("allowance"),
+        "{}",
+        err
+    );
+
+    let allowance_res = service
+        .get_allowance(context, GetAllowancePayload {
+            asset_id: asset.id,
+            grantor:  caller,
+            grantee:  to_address,
+        })
+        .unwrap();
+    assert_eq!(allowance_res.value, 24);
+}
+
+#[test]
+fn test_transfer_to_reserved_recipient() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let treasury = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 user.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+
+    // Only issuer can reserve an address
+    let user_context = mock_context(cycles_limit, user);
+    let reserve_payload = ReservedRecipientPayload {
+        asset_id: asset.id.clone(),
+        address:  treasury.clone(),
+    };
+    assert!(service
+        .add_reserved_recipient(user_context.clone(), reserve_payload.clone())
+        .is_err());
+    service
+        .add_reserved_recipient(context.clone(), reserve_payload.clone())
+        .unwrap();
+
+    let transfer_payload = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 treasury.clone(),
+        value:              24,
+        valid_until_height: None,
+    };
+    assert!(service
+        .transfer(user_context.clone(), transfer_payload.clone())
+        .is_err());
+    service
+        .transfer(context.clone(), transfer_payload.clone())
+        .unwrap();
+
+    service
+        .remove_reserved_recipient(context.clone(), reserve_payload)
+        .unwrap();
+    service.transfer(user_context, transfer_payload).unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user:     treasury,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 48);
+}
+
+#[test]
+fn test_set_asset_paused() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user_context = mock_context(cycles_limit, user.clone());
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       user.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    // Only issuer can pause an asset
+    let pause_payload = SetAssetPausedPayload {
+        asset_id: asset.id.clone(),
+        paused:   true,
+    };
+    assert!(service
+        .set_asset_paused(user_context.clone(), pause_payload.clone())
+        .is_err());
+    service
+        .set_asset_paused(context.clone(), pause_payload)
+        .unwrap();
+    assert!(
+        service
+            .get_asset(context.clone(), GetAssetPayload {
+                id: asset.id.clone(),
+            })
+            .unwrap()
+            .paused
+    );
+
+    let transfer_payload = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 user.clone(),
+        value:              24,
+        valid_until_height: None,
+    };
+    let res = service
+        .preflight_transfer(context.clone(), transfer_payload.clone())
+        .unwrap();
+    assert_eq!(res.failing_check, Some("asset_paused".to_owned()));
+    assert!(service
+        .transfer(context.clone(), transfer_payload.clone())
+        .is_err());
+    assert!(service
+        .transfer_from(user_context, TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    issuer,
+            recipient: user.clone(),
+            value:     24,
+        })
+        .is_err());
+
+    service
+        .set_asset_paused(context.clone(), SetAssetPausedPayload {
+            asset_id: asset.id.clone(),
+            paused:   false,
+        })
+        .unwrap();
+    service.transfer(context.clone(), transfer_payload).unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 24);
+}
+
+#[test]
+fn test_set_frozen() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let spender = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user_context = mock_context(cycles_limit, user.clone());
+    service
+        .approve(user_context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       spender.clone(),
+            value:    1024,
+        })
+        .unwrap();
+
+    // Only issuer can freeze a balance
+    let freeze_payload = SetFrozenPayload {
+        asset_id: asset.id.clone(),
+        address:  user.clone(),
+        frozen:   true,
+    };
+    assert!(service
+        .set_frozen(user_context.clone(), freeze_payload.clone())
+        .is_err());
+    service.set_frozen(context.clone(), freeze_payload).unwrap();
+
+    // Frozen account can still receive
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 user.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+
+    let back_payload = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 issuer.clone(),
+        value:              24,
+        valid_until_height: None,
+    };
+    assert!(service
+        .transfer(user_context.clone(), back_payload.clone())
+        .is_err());
+    assert!(service
+        .transfer_from(mock_context(cycles_limit, spender), TransferFromPayload {
+            asset_id:  asset.id.clone(),
+            sender:    user.clone(),
+            recipient: issuer,
+            value:     24,
+        })
+        .is_err());
+    let res = service
+        .preflight_transfer(user_context.clone(), back_payload.clone())
+        .unwrap();
+    assert_eq!(res.failing_check, Some("sender_frozen".to_owned()));
+
+    service
+        .set_frozen(context.clone(), SetFrozenPayload {
+            asset_id: asset.id.clone(),
+            address:  user.clone(),
+            frozen:   false,
+        })
+        .unwrap();
+    service.transfer(user_context, back_payload).unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1000);
+}
+
+#[test]
+fn test_blacklist() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let user_context = mock_context(cycles_limit, user.clone());
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 user.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+
+    // Only issuer can manage blacklist
+    let blacklist_payload = BlacklistPayload {
+        asset_id: asset.id.clone(),
+        address:  user.clone(),
+    };
+    assert!(service
+        .add_to_blacklist(user_context.clone(), blacklist_payload.clone())
+        .is_err());
+    service
+        .add_to_blacklist(context.clone(), blacklist_payload.clone())
+        .unwrap();
+
+    // Blacklisted recipient can't receive even from issuer
+    let to_user = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 user.clone(),
+        value:              24,
+        valid_until_height: None,
+    };
+    assert!(service.transfer(context.clone(), to_user.clone()).is_err());
+    let res = service
+        .preflight_transfer(context.clone(), to_user.clone())
+        .unwrap();
+    assert_eq!(res.failing_check, Some("recipient_blacklisted".to_owned()));
+
+    let from_user = TransferPayload {
+        asset_id:           asset.id.clone(),
+        to:                 issuer,
+        value:              24,
+        valid_until_height: None,
+    };
+    assert!(service
+        .transfer(user_context.clone(), from_user.clone())
+        .is_err());
+
+    service
+        .remove_from_blacklist(context.clone(), blacklist_payload)
+        .unwrap();
+    service.transfer(context.clone(), to_user).unwrap();
+    service.transfer(user_context, from_user).unwrap();
+
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id,
+            user,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1024);
+}
+
+#[test]
+fn test_update_asset_meta() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer);
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let rename_payload = UpdateAssetMetaPayload {
+        asset_id: asset.id.clone(),
+        name:     Some("renamed".to_owned()),
+        symbol:   None,
+    };
+    assert!(service
+        .update_asset_meta(mock_context(cycles_limit, user), rename_payload.clone())
+        .is_err());
+    service
+        .update_asset_meta(context.clone(), rename_payload)
+        .unwrap();
+
+    // Nothing to update
+    assert!(service
+        .update_asset_meta(context.clone(), UpdateAssetMetaPayload {
+            asset_id: asset.id.clone(),
+            name:     None,
+            symbol:   None,
+        })
+        .is_err());
+
+    // Same name and symbol limits as create_asset
+    for (name, symbol) in &[
+        (Some(String::new()), None),
+        (Some("n".repeat(65)), None),
+        (None, Some(String::new())),
+        (None, Some("S".repeat(17))),
+    ] {
+        let err = service
+            .update_asset_meta(context.clone(), UpdateAssetMetaPayload {
+                asset_id: asset.id.clone(),
+                name:     name.clone(),
+                symbol:   symbol.clone(),
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("MeaningLessValue"), "{}", err);
+    }
+
+    let updated = service
+        .get_asset(context, GetAssetPayload {
+            id: asset.id.clone(),
+        })
+        .unwrap();
+    assert_eq!(updated.name, "renamed");
+    assert_eq!(updated.symbol, asset.symbol);
+    assert_eq!(updated.supply, asset.supply);
+}
+
+#[test]
+fn test_issuer_handover() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let issuer = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, issuer.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let new_issuer = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let stranger = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let new_issuer_context = mock_context(cycles_limit, new_issuer.clone());
+
+    // Only issuer can propose
+    let propose_payload = ProposeIssuerPayload {
+        asset_id:   asset.id.clone(),
+        new_issuer: new_issuer.clone(),
+    };
+    assert!(service
+        .propose_issuer(new_issuer_context.clone(), propose_payload.clone())
+        .is_err());
+    service
+        .propose_issuer(context.clone(), propose_payload)
+        .unwrap();
+
+    // Issuer is unchanged until accepted
+    let get_asset_payload = GetAssetPayload {
+        id: asset.id.clone(),
+    };
+    let pending = service
+        .get_asset(context.clone(), get_asset_payload.clone())
+        .unwrap();
+    assert_eq!(pending.issuer, issuer);
+    assert_eq!(pending.pending_issuer, Some(new_issuer.clone()));
+
+    let accept_payload = AcceptIssuerPayload {
+        asset_id: asset.id.clone(),
+    };
+    assert!(service
+        .accept_issuer(mock_context(cycles_limit, stranger), accept_payload.clone())
+        .is_err());
+    service
+        .accept_issuer(new_issuer_context, accept_payload.clone())
+        .unwrap();
+
+    let accepted = service
+        .get_asset(context.clone(), get_asset_payload)
+        .unwrap();
+    assert_eq!(accepted.issuer, new_issuer);
+    assert_eq!(accepted.pending_issuer, None);
+
+    // Old issuer lost control
+    assert!(service
+        .set_asset_paused(context, SetAssetPausedPayload {
+            asset_id: asset.id,
+            paused:   true,
+        })
+        .is_err());
+}
+
+#[test]
+fn test_preflight_transfer() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let to_address = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let treasury = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    let preflight = |service: &AssetService<_>, caller: &Address, to: &Address, value: u128| {
+        service
+            .preflight_transfer(
+                mock_context(cycles_limit, caller.clone()),
+                TransferPayload {
+                    asset_id: asset.id.clone(),
+                    to: to.clone(),
+                    value,
+                    valid_until_height: None,
+                },
+            )
+            .unwrap()
+    };
+
+    let res = preflight(&service, &caller, &to_address, 1024);
+    assert!(res.ok);
+    assert_eq!(res.failing_check, None);
+
+    let res = service
+        .preflight_transfer(context.clone(), TransferPayload {
+            asset_id:           Hash::digest(Bytes::from("not exists")),
+            to:                 to_address.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+    assert!(!res.ok);
+    assert_eq!(res.failing_check, Some("asset_not_found".to_owned()));
+
+    let res = preflight(&service, &caller, &caller, 1024);
+    assert_eq!(res.failing_check, Some("recipient_is_sender".to_owned()));
+
+    let res = preflight(&service, &to_address, &caller, 1);
+    assert_eq!(res.failing_check, Some("insufficient_balance".to_owned()));
+
+    service
+        .add_reserved_recipient(context.clone(), ReservedRecipientPayload {
+            asset_id: asset.id.clone(),
+            address:  treasury.clone(),
+        })
+        .unwrap();
+    let res = preflight(&service, &to_address, &treasury, 0);
+    assert_eq!(res.failing_check, Some("recipient_reserved".to_owned()));
+
+    let user = Address::from_hex("0x888cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .set_frozen(context.clone(), SetFrozenPayload {
+            asset_id: asset.id.clone(),
+            address:  user.clone(),
+            frozen:   true,
+        })
+        .unwrap();
+    let res = preflight(&service, &user, &to_address, 0);
+    assert_eq!(res.failing_check, Some("sender_frozen".to_owned()));
+
+    let blacklisted = Address::from_hex("0x999cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .add_to_blacklist(context.clone(), BlacklistPayload {
+            asset_id: asset.id.clone(),
+            address:  blacklisted.clone(),
+        })
+        .unwrap();
+    let res = preflight(&service, &blacklisted, &to_address, 0);
+    assert_eq!(res.failing_check, Some("sender_blacklisted".to_owned()));
+    let res = preflight(&service, &caller, &blacklisted, 1);
+    assert_eq!(res.failing_check, Some("recipient_blacklisted".to_owned()));
+
+    service
+        .set_asset_paused(context.clone(), SetAssetPausedPayload {
+            asset_id: asset.id.clone(),
+            paused:   true,
+        })
+        .unwrap();
+    let res = preflight(&service, &caller, &to_address, 1);
+    assert_eq!(res.failing_check, Some("asset_paused".to_owned()));
+    service
+        .set_asset_paused(context.clone(), SetAssetPausedPayload {
+            asset_id: asset.id.clone(),
+            paused:   false,
+        })
+        .unwrap();
+
+    // Total supply can't overflow a balance through transfers, seed it directly
+    service
+        .sdk
+        .set_account_value(&to_address, asset.id.clone(), AssetBalance {
+            value:     u128::max_value(),
+            allowance: BTreeMap::new(),
+        })
+        .unwrap();
+    let res = preflight(&service, &caller, &to_address, 1);
+    assert_eq!(res.failing_check, Some("recipient_overflow".to_owned()));
+
+    // Preflight never mutates state
+    let balance_res = service
+        .get_balance(context, GetBalancePayload {
+            asset_id: asset.id.clone(),
+            user:     caller,
+        })
+        .unwrap();
+    assert_eq!(balance_res.balance, 1024 * 1024);
+}
+
+#[test]
+fn test_get_allowances() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let grantee_a = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let grantee_b = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    for (grantee, value) in &[(grantee_a.clone(), 24), (grantee_b.clone(), 1024)] {
+        service
+            .approve(context.clone(), ApprovePayload {
+                asset_id: asset.id.clone(),
+                to:       grantee.clone(),
+                value:    *value,
+            })
+            .unwrap();
+    }
+
+    let res = service
+        .get_allowances(context.clone(), GetAllowancesPayload {
+            asset_id: asset.id.clone(),
+            grantor:  caller,
+        })
+        .unwrap();
+    assert_eq!(res.allowances.len(), 2);
+    assert!(res.allowances.contains(&(grantee_a.clone(), 24)));
+    assert!(res.allowances.contains(&(grantee_b, 1024)));
+
+    // No approval granted
+    let res = service
+        .get_allowances(context, GetAllowancesPayload {
+            asset_id: asset.id,
+            grantor:  grantee_a,
+        })
+        .unwrap();
+    assert!(res.allowances.is_empty());
+}
+
+#[test]
+fn test_get_total_outstanding_allowance() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let grantor = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let grantee = Address::from_hex("0x777cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       grantee.clone(),
+            value:    100,
+        })
+        .unwrap();
+    service
+        .approve(context.clone(), ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       grantor.clone(),
+            value:    50,
+        })
+        .unwrap();
+
+    let grantor_context = mock_context(cycles_limit, grantor.clone());
+    service
+        .approve(grantor_context, ApprovePayload {
+            asset_id: asset.id.clone(),
+            to:       grantee,
+            value:    30,
+        })
+        .unwrap();
+
+    let total_res = service
+        .get_total_outstanding_allowance(context, GetTotalOutstandingAllowancePayload {
+            asset_id: asset.id.clone(),
+            grantors: vec![caller, grantor],
+        })
+        .unwrap();
+    assert_eq!(total_res.asset_id, asset.id);
+    assert_eq!(total_res.total, 180);
+}
+
+#[test]
+fn test_verify_asset_integrity() {
+    let cycles_limit = 1024 * 1024 * 1024; // 1073741824
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let context = mock_context(cycles_limit, caller.clone());
+
+    let mut service = new_asset_service();
+
+    let asset = service
+        .create_asset(context.clone(), CreateAssetPayload {
+            name:   "test".to_owned(),
+            symbol: "test".to_owned(),
+            supply: 1024 * 1024,
+            memo:   String::new(),
+        })
+        .unwrap();
+
+    let user = Address::from_hex("0x666cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    service
+        .transfer(context.clone(), TransferPayload {
+            asset_id:           asset.id.clone(),
+            to:                 user.clone(),
+            value:              1024,
+            valid_until_height: None,
+        })
+        .unwrap();
+
+    let verify = |service: &AssetService<_>, holders: Vec<Address>| {
+        service
+            .verify_asset_integrity(context.clone(), VerifyAssetIntegrityPayload {
+                asset_id: asset.id.clone(),
+                holders,
+            })
+            .unwrap()
+    };
+
+    let report = verify(&service, vec![caller.clone(), user.clone(), user.clone()]);
+    assert!(report.consistent);
+    assert_eq!(report.balance_sum, asset.supply);
+    assert_eq!(report.holders_with_balance, 2);
+    assert_eq!(report.holder_count, Some(2));
+
+    // Missing holder
+    let report = verify(&service, vec![caller.clone()]);
+    assert!(!report.consistent);
+    assert_eq!(report.balance_sum, asset.supply - 1024);
+
+    // Balance changed without going through transfer
+    service
+        .sdk
+        .set_account_value(&user, asset.id.clone(), AssetBalance {
+            value:     2048,
+            allowance: BTreeMap::new(),
+        })
+        .unwrap();
+    let report = verify(&service, vec![caller, user]);
+    assert!(!report.consistent);
+    assert_eq!(report.balance_sum, asset.supply + 1024);
+}
+
+fn new_asset_service() -> AssetService<
+    DefalutServiceSDK<
+        GeneralServiceState<MemoryDB>,
+        DefaultChainQuerier<MockStorage>,
+        NoopDispatcher,
+    >,
+> {
+    let chain_db = DefaultChainQuerier::new(Arc::new(MockStorage {}));
+    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
+    let state = GeneralServiceState::new(trie);
+
+    let sdk = DefalutServiceSDK::new(
+        Rc::new(RefCell::new(state)),
+        Rc::new(chain_db),
+        NoopDispatcher {},
+    );
+
+    AssetService::new(sdk).unwrap()
+}
+
+fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
+    mock_context_at_height(cycles_limit, caller, 1)
+}
+
+fn mock_context_at_height(cycles_limit: u64, caller: Address, height: u64) -> ServiceContext {
     let params = ServiceContextParams {
         tx_hash: None,
         nonce: None,
@@ -230,7 +1627,7 @@ fn mock_context(cycles_limit: u64, caller: Address) -> ServiceContext {
         cycles_price: 1,
         cycles_used: Rc::new(RefCell::new(0)),
         caller,
-        height: 1,
+        height,
         timestamp: 0,
         service_name: "service_name".to_owned(),
         service_method: "service_method".to_owned(),
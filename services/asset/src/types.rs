@@ -14,15 +14,26 @@ pub struct InitGenesisPayload {
     pub id:     Hash,
     pub name:   String,
     pub symbol: String,
-    pub supply: u64,
+    pub supply: u128,
     pub issuer: Address,
+    #[serde(default)]
+    pub memo:   String,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct CreateAssetPayload {
     pub name:   String,
     pub symbol: String,
-    pub supply: u64,
+    pub supply: u128,
+    // Skipped when empty so asset ids derived from memo-less payloads don't change
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub memo:   String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ComputeAssetIdPayload {
+    pub caller: Address,
+    pub asset:  CreateAssetPayload,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -32,9 +43,11 @@ pub struct GetAssetPayload {
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct TransferPayload {
-    pub asset_id: Hash,
-    pub to:       Address,
-    pub value:    u64,
+    pub asset_id:           Hash,
+    pub to:                 Address,
+    pub value:              u128,
+    #[serde(default)]
+    pub valid_until_height: Option<u64>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -42,17 +55,22 @@ pub struct TransferEvent {
     pub asset_id: Hash,
     pub from:     Address,
     pub to:       Address,
-    pub value:    u64,
+    pub value:    u128,
 }
 
-pub type ApprovePayload = TransferPayload;
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApprovePayload {
+    pub asset_id: Hash,
+    pub to:       Address,
+    pub value:    u128,
+}
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct ApproveEvent {
     pub asset_id: Hash,
     pub grantor:  Address,
     pub grantee:  Address,
-    pub value:    u64,
+    pub value:    u128,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -60,7 +78,7 @@ pub struct TransferFromPayload {
     pub asset_id:  Hash,
     pub sender:    Address,
     pub recipient: Address,
-    pub value:     u64,
+    pub value:     u128,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -69,7 +87,7 @@ pub struct TransferFromEvent {
     pub caller:    Address,
     pub sender:    Address,
     pub recipient: Address,
-    pub value:     u64,
+    pub value:     u128,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -82,7 +100,13 @@ pub struct GetBalancePayload {
 pub struct GetBalanceResponse {
     pub asset_id: Hash,
     pub user:     Address,
-    pub balance:  u64,
+    pub balance:  u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetBalancesPayload {
+    pub asset_id: Hash,
+    pub users:    Vec<Address>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -97,48 +121,235 @@ pub struct GetAllowanceResponse {
     pub asset_id: Hash,
     pub grantor:  Address,
     pub grantee:  Address,
-    pub value:    u64,
+    pub value:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAllowancesPayload {
+    pub asset_id: Hash,
+    pub grantor:  Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAllowancesResponse {
+    pub asset_id:   Hash,
+    pub grantor:    Address,
+    pub allowances: Vec<(Address, u128)>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetTotalOutstandingAllowancePayload {
+    pub asset_id: Hash,
+    pub grantors: Vec<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetTotalOutstandingAllowanceResponse {
+    pub asset_id: Hash,
+    pub total:    u128,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAssetsPayload {
+    pub offset: u64,
+    pub limit:  u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAssetsResponse {
+    pub assets: Vec<Asset>,
+    pub total:  u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PreflightTransferResponse {
+    pub ok:            bool,
+    pub failing_check: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReservedRecipientPayload {
+    pub asset_id: Hash,
+    pub address:  Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetReservedRecipientEvent {
+    pub asset_id: Hash,
+    pub address:  Address,
+    pub reserved: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetAssetPausedPayload {
+    pub asset_id: Hash,
+    pub paused:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetAssetPausedEvent {
+    pub asset_id: Hash,
+    pub paused:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BlacklistPayload {
+    pub asset_id: Hash,
+    pub address:  Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetBlacklistedEvent {
+    pub asset_id:    Hash,
+    pub address:     Address,
+    pub blacklisted: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetFrozenPayload {
+    pub asset_id: Hash,
+    pub address:  Address,
+    pub frozen:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetFrozenEvent {
+    pub asset_id: Hash,
+    pub address:  Address,
+    pub frozen:   bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpdateAssetMetaPayload {
+    pub asset_id: Hash,
+    pub name:     Option<String>,
+    pub symbol:   Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UpdateAssetMetaEvent {
+    pub asset_id: Hash,
+    pub name:     String,
+    pub symbol:   String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProposeIssuerPayload {
+    pub asset_id:   Hash,
+    pub new_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProposeIssuerEvent {
+    pub asset_id:       Hash,
+    pub issuer:         Address,
+    pub pending_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AcceptIssuerPayload {
+    pub asset_id: Hash,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AcceptIssuerEvent {
+    pub asset_id:   Hash,
+    pub old_issuer: Address,
+    pub new_issuer: Address,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct VerifyAssetIntegrityPayload {
+    pub asset_id: Hash,
+    // Every address that has held the asset, e.g. collected from transfer events
+    pub holders:  Vec<Address>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AssetIntegrityReport {
+    pub asset_id:             Hash,
+    pub supply:               u128,
+    pub balance_sum:          u128,
+    pub holder_count:         Option<u64>,
+    pub holders_with_balance: u64,
+    pub consistent:           bool,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct Asset {
-    pub id:     Hash,
-    pub name:   String,
-    pub symbol: String,
-    pub supply: u64,
-    pub issuer: Address,
+    pub id:             Hash,
+    pub name:           String,
+    pub symbol:         String,
+    pub supply:         u128,
+    pub issuer:         Address,
+    pub paused:         bool,
+    pub pending_issuer: Option<Address>,
+    pub memo:           String,
+    // None for assets stored before holder tracking, their count is unknown
+    pub holder_count:   Option<u64>,
 }
 
 pub struct AssetBalance {
-    pub value:     u64,
-    pub allowance: BTreeMap<Address, u64>,
+    pub value:     u128,
+    pub allowance: BTreeMap<Address, u128>,
 }
 
 struct AllowanceCodec {
     pub addr:  Address,
-    pub total: u64,
+    pub total: u128,
 }
 
 impl rlp::Decodable for Asset {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         Ok(Self {
-            id:     rlp.at(0)?.as_val()?,
-            name:   rlp.at(1)?.as_val()?,
-            symbol: rlp.at(2)?.as_val()?,
-            supply: rlp.at(3)?.as_val()?,
-            issuer: rlp.at(4)?.as_val()?,
+            id:             rlp.at(0)?.as_val()?,
+            name:           rlp.at(1)?.as_val()?,
+            symbol:         rlp.at(2)?.as_val()?,
+            supply:         decode_u128(&rlp.at(3)?)?,
+            issuer:         rlp.at(4)?.as_val()?,
+            // Assets stored before pause support have no paused item
+            paused:         if rlp.item_count()? > 5 {
+                rlp.at(5)?.as_val()?
+            } else {
+                false
+            },
+            pending_issuer: if rlp.item_count()? > 6 {
+                rlp.at(6)?.as_list::<Address>()?.pop()
+            } else {
+                None
+            },
+            memo:           if rlp.item_count()? > 7 {
+                rlp.at(7)?.as_val()?
+            } else {
+                String::new()
+            },
+            // Assets stored before holder tracking have unknown holder count
+            holder_count:   if rlp.item_count()? > 8 {
+                rlp.at(8)?.as_list::<u64>()?.pop()
+            } else {
+                None
+            },
         })
     }
 }
 
 impl rlp::Encodable for Asset {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(5)
+        s.begin_list(9)
             .append(&self.id)
             .append(&self.name)
             .append(&self.symbol)
-            .append(&self.supply)
-            .append(&self.issuer);
+            .append(&encode_u128(self.supply))
+            .append(&self.issuer)
+            .append(&self.paused);
+
+        // Encode optional pending issuer as a list of zero or one item
+        let pending_issuer: Vec<Address> = self.pending_issuer.iter().cloned().collect();
+        s.append_list(&pending_issuer);
+        s.append(&self.memo);
+
+        let holder_count: Vec<u64> = self.holder_count.iter().cloned().collect();
+        s.append_list(&holder_count);
     }
 }
 
@@ -156,20 +367,22 @@ impl rlp::Decodable for AllowanceCodec {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         Ok(Self {
             addr:  rlp.at(0)?.as_val()?,
-            total: rlp.at(1)?.as_val()?,
+            total: decode_u128(&rlp.at(1)?)?,
         })
     }
 }
 
 impl rlp::Encodable for AllowanceCodec {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(2).append(&self.addr).append(&self.total);
+        s.begin_list(2)
+            .append(&self.addr)
+            .append(&encode_u128(self.total));
     }
 }
 
 impl rlp::Decodable for AssetBalance {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
-        let value = rlp.at(0)?.as_val()?;
+        let value = decode_u128(&rlp.at(0)?)?;
         let codec_list: Vec<AllowanceCodec> = rlp::decode_list(rlp.at(1)?.as_raw());
         let mut allowance = BTreeMap::new();
         for v in codec_list {
@@ -183,7 +396,7 @@ impl rlp::Decodable for AssetBalance {
 impl rlp::Encodable for AssetBalance {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
         s.begin_list(2);
-        s.append(&self.value);
+        s.append(&encode_u128(self.value));
 
         let mut codec_list = Vec::with_capacity(self.allowance.len());
 
@@ -209,3 +422,25 @@ impl FixedCodec for AssetBalance {
         Ok(rlp::decode(bytes.as_ref()).map_err(FixedCodecError::from)?)
     }
 }
+
+// rlp doesn't support u128, encode it the same way as other integers: big
+// endian without leading zeros. Balances stored as u64 decode unchanged.
+fn encode_u128(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let leading_zeros = bytes.iter().take_while(|b| **b == 0).count();
+    bytes[leading_zeros..].to_vec()
+}
+
+fn decode_u128(rlp: &rlp::Rlp) -> Result<u128, rlp::DecoderError> {
+    let data = rlp.data()?;
+    if data.len() > 16 {
+        return Err(rlp::DecoderError::RlpIsTooBig);
+    }
+    if data.first() == Some(&0) {
+        return Err(rlp::DecoderError::RlpInvalidIndirection);
+    }
+
+    let mut bytes = [0u8; 16];
+    bytes[16 - data.len()..].copy_from_slice(data);
+    Ok(u128::from_be_bytes(bytes))
+}
@@ -4,36 +4,66 @@ pub mod types;
 pub mod vm;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::Duration;
 
 use derive_more::{Display, From};
+use serde::{de::DeserializeOwned, Serialize};
 
-use binding_macro::{read, service, write};
+use binding_macro::{genesis, read, service, write};
 use protocol::traits::ExecutorParams;
 use protocol::traits::ServiceSDK;
 use protocol::types::{Address, Hash, ServiceContext};
 use protocol::{Bytes, BytesMut, ProtocolError, ProtocolErrorKind, ProtocolResult};
 
-use crate::types::{Contract, DeployPayload, DeployResp, ExecPayload};
+use crate::types::{
+    Contract, DeployPayload, DeployResp, DeployWithSaltPayload, EstimateExecResp, ExecPayload,
+    InitGenesisPayload, InterpreterType, RiscvStats,
+};
 use crate::vm::{ChainInterface, Interpreter, InterpreterConf, InterpreterParams};
 
+const CONTRACT_COUNT_KEY: &str = "contract_count";
+const TOTAL_CODE_BYTES_KEY: &str = "total_code_bytes";
+const CALL_TIMEOUT_MS_KEY: &str = "call_timeout_ms";
+
 pub struct RiscvService<SDK> {
-    sdk: Rc<RefCell<SDK>>,
+    sdk:  Rc<RefCell<SDK>>,
+    conf: InterpreterConf,
 }
 
 #[service]
 impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
     pub fn init(sdk: SDK) -> ProtocolResult<Self> {
+        let timeout = sdk
+            .get_value::<String, u64>(&CALL_TIMEOUT_MS_KEY.to_owned())?
+            .map(Duration::from_millis);
+
         Ok(Self {
-            sdk: Rc::new(RefCell::new(sdk)),
+            sdk:  Rc::new(RefCell::new(sdk)),
+            conf: InterpreterConf {
+                timeout,
+                ..InterpreterConf::default()
+            },
         })
     }
 
-    fn run(
+    #[genesis]
+    fn init_genesis(&mut self, payload: InitGenesisPayload) -> ProtocolResult<()> {
+        if let Some(timeout_ms) = payload.call_timeout_ms {
+            self.sdk
+                .borrow_mut()
+                .set_value(CALL_TIMEOUT_MS_KEY.to_owned(), timeout_ms)?;
+            self.conf.timeout = Some(Duration::from_millis(timeout_ms));
+        }
+        Ok(())
+    }
+
+    fn run_interpreter(
         &self,
         ctx: ServiceContext,
         payload: ExecPayload,
-        is_init: bool,
+        mode: RunMode,
     ) -> ProtocolResult<String> {
         let contract = self
             .sdk
@@ -45,25 +75,65 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
             .borrow()
             .get_value::<Hash, Bytes>(&contract.code_hash)?
             .unwrap();
+        let chain_impl = ChainInterfaceImpl::new(
+            ctx.clone(),
+            payload.clone(),
+            Rc::<RefCell<_>>::clone(&self.sdk),
+        );
+        let chain: Rc<RefCell<dyn ChainInterface>> = if mode == RunMode::Estimate {
+            Rc::new(RefCell::new(BufferedChain::new(chain_impl, false)))
+        } else {
+            Rc::new(RefCell::new(chain_impl))
+        };
+
+        self.run_contract(ctx, &payload, &contract, code, mode, chain)
+    }
+
+    fn run_contract(
+        &self,
+        ctx: ServiceContext,
+        payload: &ExecPayload,
+        contract: &Contract,
+        code: Bytes,
+        mode: RunMode,
+        chain: Rc<RefCell<dyn ChainInterface>>,
+    ) -> ProtocolResult<String> {
+        if payload.call_depth >= self.conf.max_call_depth {
+            return Err(ServiceError::CallDepthExceeded(self.conf.max_call_depth).into());
+        }
+
+        // Wall-clock timeout would make transaction results differ across
+        // nodes, so it only guards read-only calls
+        let mut conf = self.conf.clone();
+        if mode == RunMode::Exec || mode == RunMode::Init {
+            conf.timeout = None;
+        }
+
         let interpreter_params = InterpreterParams {
             address: payload.address.clone(),
             code,
             args: payload.args.clone().into(),
-            is_init,
+            is_init: mode == RunMode::Init,
+            max_cycles: contract.max_cycles,
         };
         let mut interpreter = Interpreter::new(
             ctx.clone(),
-            InterpreterConf::default(),
+            conf,
             contract.intp_type,
             interpreter_params,
-            Rc::new(RefCell::new(ChainInterfaceImpl::new(
-                ctx.clone(),
-                payload,
-                Rc::<RefCell<_>>::clone(&self.sdk),
-            ))),
+            chain,
         );
 
-        let r = interpreter.run().map_err(ServiceError::CkbVm)?;
+        let cycles_before = ctx.get_cycles_used();
+        let r = interpreter.run().map_err(|e| match e {
+            vm::Error::Timeout => ServiceError::Timeout,
+            vm::Error::ContractOutOfCycles(cap) => ServiceError::ContractOutOfCycles(cap),
+            vm::Error::VM(e) => ServiceError::CkbVm(e),
+            vm::Error::ExitCodeError => ServiceError::CkbVm(ckb_vm::Error::Unexpected),
+            vm::Error::UnsupportedInterpreter(t) => ServiceError::UnsupportedInterpreter(t),
+            #[cfg(feature = "wasm")]
+            vm::Error::InvalidWasmModule => ServiceError::InvalidWasmModule,
+        })?;
         let ret = String::from_utf8_lossy(r.ret.as_ref()).to_string();
         if r.ret_code != 0 {
             return Err(ServiceError::NonZeroExitCode {
@@ -72,18 +142,47 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
             }
             .into());
         }
-        ctx.sub_cycles(r.cycles_used)?;
+        // Cycles before each nested call were already charged by the chain
+        // interface
+        let charged = ctx.get_cycles_used() - cycles_before;
+        ctx.sub_cycles(r.cycles_used.saturating_sub(charged))?;
         Ok(ret)
     }
 
     #[read]
     fn call(&self, ctx: ServiceContext, payload: ExecPayload) -> ProtocolResult<String> {
-        self.run(ctx, payload, false)
+        self.run_interpreter(ctx, payload, RunMode::Call)
+    }
+
+    // Run as exec does, but storage writes are discarded, so the cost of an
+    // exec can be known before sending it. Nested calls would write through
+    // the dispatcher, so they are rejected.
+    #[read]
+    fn estimate_exec(
+        &self,
+        ctx: ServiceContext,
+        payload: ExecPayload,
+    ) -> ProtocolResult<EstimateExecResp> {
+        let cycles_before = ctx.get_cycles_used();
+        let ret = self.run_interpreter(ctx.clone(), payload, RunMode::Estimate)?;
+
+        Ok(EstimateExecResp {
+            cycles_used: ctx.get_cycles_used() - cycles_before,
+            ret,
+        })
     }
 
     #[write]
     fn exec(&mut self, ctx: ServiceContext, payload: ExecPayload) -> ProtocolResult<String> {
-        self.run(ctx, payload, false)
+        self.run_interpreter(ctx, payload, RunMode::Exec)
+    }
+
+    #[read]
+    fn get_riscv_stats(&self, ctx: ServiceContext) -> ProtocolResult<RiscvStats> {
+        Ok(RiscvStats {
+            contract_count:   self.get_stat(CONTRACT_COUNT_KEY)?,
+            total_code_bytes: self.get_stat(TOTAL_CODE_BYTES_KEY)?,
+        })
     }
 
     #[write]
@@ -92,14 +191,9 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
         ctx: ServiceContext,
         payload: DeployPayload,
     ) -> ProtocolResult<DeployResp> {
+        Self::check_intp_type(payload.intp_type)?;
         let code = Bytes::from(hex::decode(&payload.code).map_err(ServiceError::HexDecode)?);
-
-        // Save code
         let code_hash = Hash::digest(code.clone());
-        let code_len = code.len() as u64;
-        // Every bytes cost 10 cycles
-        ctx.sub_cycles(code_len * 10)?;
-        self.sdk.borrow_mut().set_value(code_hash.clone(), code)?;
 
         let tx_hash = ctx
             .get_tx_hash()
@@ -108,45 +202,184 @@ impl<SDK: ServiceSDK + 'static> RiscvService<SDK> {
         let contract_address =
             Address::from_bytes(Hash::digest(tx_hash.as_bytes()).as_bytes().slice(0..20))?;
 
-        let intp_type = payload.intp_type;
-        let contract = Contract::new(code_hash, intp_type);
+        let contract = Contract::new(code_hash, payload.intp_type, payload.max_cycles);
+        self.create_contract(
+            ctx,
+            contract_address,
+            contract,
+            code,
+            payload.init_args,
+            payload.call_depth,
+        )
+    }
 
-        self.sdk
-            .borrow_mut()
-            .set_value(contract_address.clone(), contract)?;
+    #[write]
+    fn deploy_with_salt(
+        &mut self,
+        ctx: ServiceContext,
+        payload: DeployWithSaltPayload,
+    ) -> ProtocolResult<DeployResp> {
+        Self::check_intp_type(payload.intp_type)?;
+        let code = Bytes::from(hex::decode(&payload.code).map_err(ServiceError::HexDecode)?);
+        let code_hash = Hash::digest(code.clone());
+
+        // Address only depends on caller, salt and code, so it can be computed
+        // before deployment
+        let mut seed = BytesMut::from(ctx.get_caller().as_bytes().as_ref());
+        seed.extend(payload.salt.as_bytes());
+        seed.extend(code_hash.as_bytes());
+        let contract_address =
+            Address::from_bytes(Hash::digest(seed.freeze()).as_bytes().slice(0..20))?;
+
+        if self
+            .sdk
+            .borrow()
+            .get_value::<Address, Contract>(&contract_address)?
+            .is_some()
+        {
+            return Err(ServiceError::ContractAddressCollision(contract_address.as_hex()).into());
+        }
+
+        let contract = Contract::new(code_hash, payload.intp_type, payload.max_cycles);
+        self.create_contract(
+            ctx,
+            contract_address,
+            contract,
+            code,
+            payload.init_args,
+            payload.call_depth,
+        )
+    }
+
+    fn check_intp_type(intp_type: InterpreterType) -> ProtocolResult<()> {
+        if !intp_type.is_enabled() {
+            return Err(ServiceError::UnsupportedInterpreter(intp_type).into());
+        }
+        Ok(())
+    }
+
+    fn save_code(
+        &mut self,
+        ctx: &ServiceContext,
+        code_hash: Hash,
+        code: Bytes,
+    ) -> ProtocolResult<()> {
+        let code_len = code.len() as u64;
+        // Every bytes cost 10 cycles
+        ctx.sub_cycles(code_len * 10)?;
+        let code_exists = self
+            .sdk
+            .borrow()
+            .get_value::<Hash, Bytes>(&code_hash)?
+            .is_some();
+        if !code_exists {
+            self.incr_stat(TOTAL_CODE_BYTES_KEY, code_len)?;
+        }
+        self.sdk.borrow_mut().set_value(code_hash, code)
+    }
+
+    fn create_contract(
+        &mut self,
+        ctx: ServiceContext,
+        contract_address: Address,
+        contract: Contract,
+        code: Bytes,
+        init_args: String,
+        call_depth: usize,
+    ) -> ProtocolResult<DeployResp> {
+        if code.len() > self.conf.max_code_size {
+            return Err(ServiceError::CodeTooLarge {
+                size: code.len(),
+                max:  self.conf.max_code_size,
+            }
+            .into());
+        }
 
-        // run init
-        let init_ret = if !payload.init_args.is_empty() {
+        // Run init before anything is persisted, its storage writes are kept
+        // in memory until it succeeds, so a failed init leaves no contract
+        let mut init_ret = String::new();
+        let mut init_chain = None;
+        if !init_args.is_empty() {
             let init_payload = ExecPayload {
                 address: contract_address.clone(),
-                args:    payload.init_args,
+                args: init_args,
+                call_depth,
             };
+            let chain_impl = ChainInterfaceImpl::new(
+                ctx.clone(),
+                init_payload.clone(),
+                Rc::<RefCell<_>>::clone(&self.sdk),
+            );
+            let chain = Rc::new(RefCell::new(BufferedChain::new(chain_impl, true)));
 
-            self.run(ctx, init_payload, true)?
-        } else {
-            String::new()
-        };
+            init_ret = self.run_contract(
+                ctx.clone(),
+                &init_payload,
+                &contract,
+                code.clone(),
+                RunMode::Init,
+                Rc::<RefCell<_>>::clone(&chain) as Rc<RefCell<dyn ChainInterface>>,
+            )?;
+            init_chain = Some(chain);
+        }
+
+        self.save_code(&ctx, contract.code_hash.clone(), code)?;
+        self.sdk
+            .borrow_mut()
+            .set_value(contract_address.clone(), contract)?;
+        self.incr_stat(CONTRACT_COUNT_KEY, 1)?;
+        if let Some(chain) = init_chain {
+            chain.borrow_mut().flush()?;
+        }
 
         Ok(DeployResp {
             address: contract_address,
             init_ret,
         })
     }
+
+    fn get_stat(&self, key: &str) -> ProtocolResult<u64> {
+        let stat = self
+            .sdk
+            .borrow()
+            .get_value::<String, u64>(&key.to_owned())?
+            .unwrap_or(0);
+        Ok(stat)
+    }
+
+    fn incr_stat(&mut self, key: &str, delta: u64) -> ProtocolResult<()> {
+        let stat = self.get_stat(key)?.saturating_add(delta);
+        self.sdk.borrow_mut().set_value(key.to_owned(), stat)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RunMode {
+    Exec,
+    Call,
+    Estimate,
+    Init,
 }
 
 struct ChainInterfaceImpl<SDK> {
     ctx:             ServiceContext,
     payload:         ExecPayload,
     sdk:             Rc<RefCell<SDK>>,
+    // Transaction cycles used before this contract runs
+    cycles_start:    u64,
+    // Cycles used by this contract run, nested calls included
     all_cycles_used: u64,
 }
 
 impl<SDK: ServiceSDK + 'static> ChainInterfaceImpl<SDK> {
     fn new(ctx: ServiceContext, payload: ExecPayload, sdk: Rc<RefCell<SDK>>) -> Self {
+        let cycles_start = ctx.get_cycles_used();
+
         Self {
             ctx,
             payload,
             sdk,
+            cycles_start,
             all_cycles_used: 0,
         }
     }
@@ -181,10 +414,7 @@ where
         args: Bytes,
         current_cycle: u64,
     ) -> ProtocolResult<(String, u64)> {
-        let payload = ExecPayload {
-            address,
-            args: String::from_utf8_lossy(args.as_ref()).to_string(),
-        };
+        let payload = ExecPayload::new(address, String::from_utf8_lossy(args.as_ref()).to_string());
         let payload_str = serde_json::to_string(&payload).map_err(ServiceError::Serde)?;
         self.service_call("riscv", "exec", &payload_str, current_cycle)
     }
@@ -196,6 +426,23 @@ where
         payload: &str,
         current_cycle: u64,
     ) -> ProtocolResult<(String, u64)> {
+        // Calls back into this service always carry the caller's depth, so a
+        // contract can't reset it through a crafted payload, deploy included
+        // since it runs init
+        let depth = self.payload.call_depth + 1;
+        let payload = match (service, method) {
+            ("riscv", "exec") | ("riscv", "call") => {
+                with_call_depth::<ExecPayload>(payload, depth, |p| &mut p.call_depth)?
+            }
+            ("riscv", "deploy") => {
+                with_call_depth::<DeployPayload>(payload, depth, |p| &mut p.call_depth)?
+            }
+            ("riscv", "deploy_with_salt") => {
+                with_call_depth::<DeployWithSaltPayload>(payload, depth, |p| &mut p.call_depth)?
+            }
+            _ => payload.to_owned(),
+        };
+
         let vm_cycle = current_cycle - self.all_cycles_used;
         self.ctx.sub_cycles(vm_cycle)?;
         let extra = self.payload.address.as_hex();
@@ -204,13 +451,95 @@ where
             Some(Bytes::from(extra)),
             service,
             method,
-            payload,
+            &payload,
         )?;
-        self.all_cycles_used = self.ctx.get_cycles_used();
+        // Keep vm cycles relative to the start of this run, so per contract
+        // cap doesn't count work done earlier in the transaction
+        self.all_cycles_used = self.ctx.get_cycles_used() - self.cycles_start;
         Ok((call_ret, self.all_cycles_used))
     }
 }
 
+fn with_call_depth<T>(
+    payload: &str,
+    depth: usize,
+    call_depth: impl FnOnce(&mut T) -> &mut usize,
+) -> ProtocolResult<String>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let mut payload: T = serde_json::from_str(payload).map_err(ServiceError::Serde)?;
+    *call_depth(&mut payload) = depth;
+    let payload = serde_json::to_string(&payload).map_err(ServiceError::Serde)?;
+    Ok(payload)
+}
+
+// Keeps storage writes of the running contract in memory until flush.
+// Nested calls write through the dispatcher and can't be buffered, so they
+// either go to the underlying chain or are rejected.
+struct BufferedChain<C> {
+    inner:         C,
+    writes:        HashMap<Bytes, Bytes>,
+    forward_calls: bool,
+}
+
+impl<C: ChainInterface> BufferedChain<C> {
+    fn new(inner: C, forward_calls: bool) -> Self {
+        Self {
+            inner,
+            writes: HashMap::new(),
+            forward_calls,
+        }
+    }
+
+    fn flush(&mut self) -> ProtocolResult<()> {
+        for (key, val) in self.writes.drain() {
+            self.inner.set_storage(key, val)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: ChainInterface> ChainInterface for BufferedChain<C> {
+    fn get_storage(&self, key: &Bytes) -> ProtocolResult<Bytes> {
+        match self.writes.get(key) {
+            Some(val) => Ok(val.clone()),
+            None => self.inner.get_storage(key),
+        }
+    }
+
+    fn set_storage(&mut self, key: Bytes, val: Bytes) -> ProtocolResult<()> {
+        self.writes.insert(key, val);
+        Ok(())
+    }
+
+    fn contract_call(
+        &mut self,
+        address: Address,
+        args: Bytes,
+        current_cycle: u64,
+    ) -> ProtocolResult<(String, u64)> {
+        if !self.forward_calls {
+            return Err(ServiceError::NestedCallNotBuffered.into());
+        }
+        self.inner.contract_call(address, args, current_cycle)
+    }
+
+    fn service_call(
+        &mut self,
+        service: &str,
+        method: &str,
+        payload: &str,
+        current_cycle: u64,
+    ) -> ProtocolResult<(String, u64)> {
+        if !self.forward_calls {
+            return Err(ServiceError::NestedCallNotBuffered.into());
+        }
+        self.inner
+            .service_call(service, method, payload, current_cycle)
+    }
+}
+
 #[derive(Debug, Display, From)]
 pub enum ServiceError {
     #[display(fmt = "method {} can not be invoke with call", _0)]
@@ -225,6 +554,31 @@ pub enum ServiceError {
     #[display(fmt = "ckb vm error: {:?}", _0)]
     CkbVm(ckb_vm::Error),
 
+    #[display(fmt = "ckb vm execution timeout")]
+    Timeout,
+
+    #[display(fmt = "contract out of cycles, cap {}", _0)]
+    ContractOutOfCycles(u64),
+
+    #[display(fmt = "nested contract or service call is not supported in estimate_exec")]
+    NestedCallNotBuffered,
+
+    #[display(fmt = "contract call depth exceeded, max {}", _0)]
+    CallDepthExceeded(usize),
+
+    #[display(fmt = "Contract address {} already exists", _0)]
+    ContractAddressCollision(String),
+
+    #[display(fmt = "interpreter {:?} is not supported by this node", _0)]
+    UnsupportedInterpreter(InterpreterType),
+
+    #[display(fmt = "code size {} exceeds limit {}", size, max)]
+    CodeTooLarge { size: usize, max: usize },
+
+    #[cfg(feature = "wasm")]
+    #[display(fmt = "invalid wasm module")]
+    InvalidWasmModule,
+
     #[display(fmt = "json serde error: {:?}", _0)]
     Serde(serde_json::error::Error),
 
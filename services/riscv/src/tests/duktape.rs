@@ -2,7 +2,7 @@ use std::{
     cell::RefCell,
     io::Read,
     rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use protocol::{
@@ -69,9 +69,11 @@ macro_rules! deploy_test_code {
         // No init
         let code = include_str!("./test_code.js");
         let payload = DeployPayload {
-            code:      hex::encode(Bytes::from(code)),
-            intp_type: InterpreterType::Duktape,
-            init_args: "".into(),
+            code:       hex::encode(Bytes::from(code)),
+            intp_type:  InterpreterType::Duktape,
+            init_args:  "".into(),
+            max_cycles: None,
+            call_depth: 0,
         };
 
         let ret = service.deploy(context.make(), payload).expect("deploy");
@@ -87,9 +89,11 @@ fn should_support_pvm_init() {
 
     let code = include_str!("./test_code.js");
     let payload = DeployPayload {
-        code:      hex::encode(Bytes::from(code)),
-        intp_type: InterpreterType::Duktape,
-        init_args: "do init".into(),
+        code:       hex::encode(Bytes::from(code)),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "do init".into(),
+        max_cycles: None,
+        call_depth: 0,
     };
 
     let ret = service.deploy(context.make(), payload).expect("deploy");
@@ -183,9 +187,11 @@ fn should_support_pvm_origin() {
     // Deploy another test code
     let code = include_bytes!("./test_code.js");
     let payload = DeployPayload {
-        code:      hex::encode(Bytes::from(code.as_ref())),
-        intp_type: InterpreterType::Duktape,
-        init_args: "".into(),
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
     };
 
     let tc_ctx = context.make();
@@ -292,6 +298,35 @@ fn should_support_pvm_timestamp() {
     assert_eq!(ret.parse::<u64>().expect("timestamp"), ctx.get_timestamp());
 }
 
+#[test]
+fn should_abort_execution_on_timeout() {
+    let (mut service, mut context, address) = deploy_test_code!();
+    service.conf.timeout = Some(Duration::from_millis(50));
+
+    // Pure compute loop, never traps into host
+    let args = json!({"method": "test_busy_loop"}).to_string();
+    let payload = ExecPayload::new(address.clone(), args);
+
+    let err = service
+        .call(context.make(), payload)
+        .expect_err("should timeout");
+    assert!(
+        err.to_string().to_lowercase().contains("timeout"),
+        "{}",
+        err
+    );
+
+    // Transactions are never cut by wall clock
+    service.conf.timeout = Some(Duration::from_millis(0));
+    let args = json!({"method": "test_cycle_price"}).to_string();
+    let payload = ExecPayload::new(address, args);
+
+    let ret = service
+        .exec(context.make(), payload)
+        .expect("exec ignores timeout");
+    assert_eq!(ret, "1");
+}
+
 #[test]
 fn should_support_pvm_emit_event() {
     let (mut service, mut context, address) = deploy_test_code!();
@@ -389,9 +424,11 @@ fn should_support_pvm_contract_call() {
     // Deploy another test code
     let code = include_bytes!("./test_code.js");
     let payload = DeployPayload {
-        code:      hex::encode(Bytes::from(code.as_ref())),
-        intp_type: InterpreterType::Duktape,
-        init_args: "".into(),
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
     };
 
     let tc_ctx = context.make();
@@ -413,6 +450,158 @@ fn should_support_pvm_contract_call() {
     assert_eq!(ret, "self");
 }
 
+#[test]
+fn should_limit_contract_call_depth() {
+    let mut context = TestContext::default();
+
+    let code = include_bytes!("./test_code.js");
+    let payload = DeployPayload {
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
+    };
+
+    let deploy_ctx = context.make();
+    let deploy_ret = with_dispatcher_service(move |dispatcher_service| {
+        dispatcher_service.deploy(deploy_ctx, payload)
+    })
+    .expect("deploy recursive test code");
+
+    // Contract calls itself until depth limit is reached
+    let args = json!({"method": "test_recursive_call"}).to_string();
+    let payload = ExecPayload::new(deploy_ret.address, args);
+
+    let exec_ctx = context.make();
+    let err = with_dispatcher_service(move |dispatcher_service| {
+        dispatcher_service.exec(exec_ctx, payload)
+    })
+    .expect_err("should exceed call depth");
+    assert!(err.to_string().to_lowercase().contains("depth"), "{}", err);
+}
+
+#[test]
+fn should_limit_call_depth_through_deploy() {
+    let mut context = TestContext::default();
+
+    let code = include_bytes!("./test_code.js");
+    let payload = DeployPayload {
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
+    };
+
+    let deploy_ctx = context.make();
+    let deploy_ret = with_dispatcher_service(move |dispatcher_service| {
+        dispatcher_service.deploy(deploy_ctx, payload)
+    })
+    .expect("deploy recursive test code");
+
+    // Every deployed contract deploys another one from its init
+    let args = json!({
+        "method": "test_recursive_deploy",
+        "code": hex::encode(Bytes::from(code.as_ref())),
+    })
+    .to_string();
+    let payload = ExecPayload::new(deploy_ret.address, args);
+
+    let exec_ctx = context.make();
+    let err = with_dispatcher_service(move |dispatcher_service| {
+        dispatcher_service.exec(exec_ctx, payload)
+    })
+    .expect_err("should exceed call depth");
+    assert!(err.to_string().to_lowercase().contains("depth"), "{}", err);
+}
+
+#[test]
+fn should_apply_contract_cycles_cap_from_contract_start() {
+    let mut context = TestContext::default();
+    let mut service = new_riscv_service();
+
+    let code = include_bytes!("./test_code.js");
+    let cap = CYCLE_LIMIT / 4;
+    let payload = DeployPayload {
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: Some(cap),
+        call_depth: 0,
+    };
+    let address = service
+        .deploy(context.make(), payload)
+        .expect("deploy capped test code")
+        .address;
+
+    let payload = DeployPayload {
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
+    };
+    let tc_ctx = context.make();
+    let tc_ret = with_dispatcher_service(move |dispatcher_service| {
+        dispatcher_service.deploy(tc_ctx, payload)
+    })
+    .expect("deploy another test code");
+
+    let args = json!({
+        "method": "test_service_call",
+        "call_service": "riscv",
+        "call_method": "exec",
+        "call_payload": json!({
+            "address": tc_ret.address.as_hex(),
+            "args": json!({
+                "method": "_ret_self",
+            }).to_string(),
+        }).to_string(),
+    })
+    .to_string();
+    let payload = ExecPayload::new(address, args);
+
+    // Transaction already used more cycles than the contract cap
+    let mut ctx_params = context.new_params();
+    ctx_params.cycles_used = Rc::new(RefCell::new(cap * 2));
+    let ctx = ServiceContext::new(ctx_params);
+
+    let ret = service
+        .exec(ctx.clone(), payload)
+        .expect("capped contract service call");
+    assert_eq!(ret, "self");
+    assert!(ctx.get_cycles_used() - cap * 2 < cap);
+}
+
+#[test]
+fn should_reject_nested_call_in_estimate_exec() {
+    let (service, mut context, address) = deploy_test_code!();
+
+    let args = json!({
+        "method": "test_service_call",
+        "call_service": "riscv",
+        "call_method": "exec",
+        "call_payload": json!({
+            "address": address.as_hex(),
+            "args": json!({
+                "method": "_ret_self",
+            }).to_string(),
+        }).to_string(),
+    })
+    .to_string();
+    let payload = ExecPayload::new(address, args);
+
+    let err = service
+        .estimate_exec(context.make(), payload)
+        .expect_err("nested call in estimate");
+    assert!(
+        err.to_string().to_lowercase().contains("estimate_exec"),
+        "{}",
+        err
+    );
+}
+
 #[test]
 fn should_support_pvm_service_call() {
     let (mut service, mut context, address) = deploy_test_code!();
@@ -420,9 +609,11 @@ fn should_support_pvm_service_call() {
     // Deploy another test code
     let code = include_bytes!("./test_code.js");
     let payload = DeployPayload {
-        code:      hex::encode(Bytes::from(code.as_ref())),
-        intp_type: InterpreterType::Duktape,
-        init_args: "".into(),
+        code:       hex::encode(Bytes::from(code.as_ref())),
+        intp_type:  InterpreterType::Duktape,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
     };
 
     let tc_ctx = context.make();
@@ -482,6 +673,8 @@ fn test_js_erc20() {
         code: hex::encode(buffer),
         intp_type: InterpreterType::Duktape,
         init_args,
+        max_cycles: None,
+        call_depth: 0,
     };
     let address = service
         .deploy(context.clone(), dep_payoad)
@@ -496,6 +689,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "1000000000".to_owned());
 
@@ -507,6 +701,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "1000000000".to_owned());
 
@@ -520,6 +715,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "".to_owned());
 
@@ -531,6 +727,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "999999900".to_owned());
 
@@ -542,6 +739,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "100".to_owned());
 
@@ -554,6 +752,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "".to_owned());
 
@@ -566,6 +765,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "1000".to_owned());
 
@@ -586,6 +786,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context2, ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "".to_owned());
 
@@ -598,6 +799,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "800".to_owned());
 
@@ -609,6 +811,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "999999700".to_owned());
 
@@ -620,6 +823,7 @@ fn test_js_erc20() {
     let exec_ret = service.exec(context.clone(), ExecPayload {
         address: address.clone(),
         args,
+        call_depth: 0,
     });
     assert_eq!(exec_ret.unwrap(), "100".to_owned());
 
@@ -628,6 +832,6 @@ fn test_js_erc20() {
         "account": to_addr2,
     })
     .to_string();
-    let exec_ret = service.exec(context, ExecPayload { address, args });
+    let exec_ret = service.exec(context, ExecPayload::new(address, args));
     assert_eq!(exec_ret.unwrap(), "200".to_owned());
 }
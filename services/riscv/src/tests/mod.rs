@@ -1,9 +1,12 @@
+#[cfg(feature = "duktape")]
 pub mod duktape;
+pub mod syscall;
 
 use std::cell::RefCell;
 use std::io::Read;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use cita_trie::MemoryDB;
@@ -16,7 +19,9 @@ use protocol::types::{
 };
 use protocol::{Bytes, ProtocolResult};
 
-use crate::types::{DeployPayload, ExecPayload, InterpreterType};
+use crate::types::{
+    DeployPayload, DeployWithSaltPayload, ExecPayload, InitGenesisPayload, InterpreterType,
+};
 use crate::RiscvService;
 
 type TestRiscvService = RiscvService<
@@ -27,18 +32,20 @@ type TestRiscvService = RiscvService<
     >,
 >;
 
+type TestState = Rc<RefCell<GeneralServiceState<MemoryDB>>>;
+
 thread_local! {
-    static RISCV_SERVICE: RefCell<TestRiscvService> = RefCell::new(new_riscv_service());
+    // Like the real dispatcher, every call gets a new service instance over
+    // shared state, so contracts can re-enter the riscv service.
+    static DISPATCHER_STATE: TestState = new_state();
 }
 
 fn with_dispatcher_service<R: for<'a> serde::Deserialize<'a>>(
     f: impl FnOnce(&mut TestRiscvService) -> ProtocolResult<R>,
 ) -> ProtocolResult<R> {
-    RISCV_SERVICE.with(|cell| {
-        let mut service = cell.borrow_mut();
+    let mut service = DISPATCHER_STATE.with(|state| new_riscv_service_with_state(Rc::clone(state)));
 
-        f(&mut service)
-    })
+    f(&mut service)
 }
 
 #[test]
@@ -58,35 +65,41 @@ fn test_deploy_and_run() {
     file.read_to_end(&mut buffer).unwrap();
     let buffer = Bytes::from(buffer);
     let deploy_payload = DeployPayload {
-        code:      hex::encode(buffer.as_ref()),
-        intp_type: InterpreterType::Binary,
-        init_args: "set k init".into(),
+        code:       hex::encode(buffer.as_ref()),
+        intp_type:  InterpreterType::Binary,
+        init_args:  "set k init".into(),
+        max_cycles: None,
+        call_depth: 0,
     };
     let deploy_result = service.deploy(context.clone(), deploy_payload).unwrap();
     assert_eq!(&deploy_result.init_ret, "");
 
     let address = deploy_result.address;
     let exec_result = service.call(context.clone(), ExecPayload {
-        address: address.clone(),
-        args:    "get k".into(),
+        address:    address.clone(),
+        args:       "get k".into(),
+        call_depth: 0,
     });
     assert_eq!(&exec_result.unwrap(), "init");
     let exec_payload = ExecPayload {
-        address: address.clone(),
-        args:    "set k v".into(),
+        address:    address.clone(),
+        args:       "set k v".into(),
+        call_depth: 0,
     };
     let exec_result = service.exec(context.clone(), exec_payload);
     assert_eq!(&exec_result.unwrap(), "");
     let exec_result = service.call(context.clone(), ExecPayload {
-        address: address.clone(),
-        args:    "get k".into(),
+        address:    address.clone(),
+        args:       "get k".into(),
+        call_depth: 0,
     });
     assert_eq!(&exec_result.unwrap(), "v");
 
     // wrong command
     let exec_result = service.exec(context.clone(), ExecPayload {
-        address: address.clone(),
-        args:    "clear k v".into(),
+        address:    address.clone(),
+        args:       "clear k v".into(),
+        call_depth: 0,
     });
     assert!(exec_result.is_err());
 
@@ -94,10 +107,415 @@ fn test_deploy_and_run() {
     let exec_result = service.exec(context, ExecPayload {
         address,
         args: "set k".into(),
+        call_depth: 0,
     });
     assert!(exec_result.is_err());
 }
 
+#[test]
+fn test_max_call_depth() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+    service.conf.max_call_depth = 2;
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(&binary),
+            intp_type:  InterpreterType::Binary,
+            init_args:  "set k init".into(),
+            max_cycles: None,
+            call_depth: 0,
+        })
+        .unwrap();
+
+    let ret = service
+        .call(context.clone(), ExecPayload {
+            address:    deploy_result.address.clone(),
+            args:       "get k".into(),
+            call_depth: 1,
+        })
+        .unwrap();
+    assert_eq!(&ret, "init");
+
+    let err = service
+        .call(context, ExecPayload {
+            address:    deploy_result.address,
+            args:       "get k".into(),
+            call_depth: 2,
+        })
+        .unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("depth"));
+}
+
+#[test]
+fn test_init_genesis_call_timeout() {
+    let state = new_state();
+
+    let mut service = new_riscv_service_with_state(Rc::clone(&state));
+    assert!(service.conf.timeout.is_none());
+
+    service
+        .init_genesis(InitGenesisPayload {
+            call_timeout_ms: Some(50),
+        })
+        .unwrap();
+
+    let service = new_riscv_service_with_state(state);
+    assert_eq!(service.conf.timeout, Some(Duration::from_millis(50)));
+}
+
+#[test]
+fn test_deploy_init_failure_leaves_no_contract() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash.clone(), nonce);
+
+    let mut service = new_riscv_service();
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let deploy_payload = DeployPayload {
+        code:       hex::encode(&binary),
+        intp_type:  InterpreterType::Binary,
+        init_args:  "clear k v".into(),
+        max_cycles: None,
+        call_depth: 0,
+    };
+    let err = service.deploy(context.clone(), deploy_payload).unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("non zero"));
+
+    // Same tx hash derives same contract address
+    let contract_address =
+        Address::from_bytes(Hash::digest(tx_hash.as_bytes()).as_bytes().slice(0..20)).unwrap();
+    let err = service
+        .exec(context.clone(), ExecPayload {
+            address:    contract_address,
+            args:       "get k".into(),
+            call_depth: 0,
+        })
+        .unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("not exists"));
+
+    let stats = service.get_riscv_stats(context).unwrap();
+    assert_eq!(stats.contract_count, 0);
+    assert_eq!(stats.total_code_bytes, 0);
+}
+
+#[test]
+fn test_estimate_exec() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller.clone(), tx_hash.clone(), nonce.clone());
+
+    let mut service = new_riscv_service();
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(&binary),
+            intp_type:  InterpreterType::Binary,
+            init_args:  "set k init".into(),
+            max_cycles: None,
+            call_depth: 0,
+        })
+        .unwrap();
+    let address = deploy_result.address;
+    let set_payload = ExecPayload {
+        address:    address.clone(),
+        args:       "set k v".into(),
+        call_depth: 0,
+    };
+
+    let estimate_context =
+        mock_context(cycles_limit, caller.clone(), tx_hash.clone(), nonce.clone());
+    let estimate = service
+        .estimate_exec(estimate_context, set_payload.clone())
+        .unwrap();
+    assert_eq!(&estimate.ret, "");
+    assert!(estimate.cycles_used > 0);
+
+    // Estimate doesn't write storage
+    let get_payload = ExecPayload {
+        address,
+        args: "get k".into(),
+        call_depth: 0,
+    };
+    let ret = service.call(context.clone(), get_payload.clone()).unwrap();
+    assert_eq!(&ret, "init");
+
+    let exec_context = mock_context(cycles_limit, caller, tx_hash, nonce);
+    service.exec(exec_context.clone(), set_payload).unwrap();
+    assert_eq!(exec_context.get_cycles_used(), estimate.cycles_used);
+
+    let ret = service.call(context, get_payload).unwrap();
+    assert_eq!(&ret, "v");
+}
+
+#[cfg(feature = "duktape")]
+#[test]
+fn test_get_riscv_stats() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+
+    let mut service = new_riscv_service();
+
+    let stats = service
+        .get_riscv_stats(mock_context(
+            cycles_limit,
+            caller.clone(),
+            Hash::digest(Bytes::from("stats")),
+            nonce.clone(),
+        ))
+        .unwrap();
+    assert_eq!(stats.contract_count, 0);
+    assert_eq!(stats.total_code_bytes, 0);
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let js = include_str!("./test_code.js");
+    let codes = vec![
+        (hex::encode(&binary), InterpreterType::Binary),
+        (hex::encode(js), InterpreterType::Duktape),
+    ];
+
+    for (idx, (code, intp_type)) in codes.into_iter().enumerate() {
+        let tx_hash = Hash::digest(Bytes::from(format!("deploy {}", idx)));
+        let context = mock_context(cycles_limit, caller.clone(), tx_hash, nonce.clone());
+
+        service
+            .deploy(context, DeployPayload {
+                code,
+                intp_type,
+                init_args: "".into(),
+                max_cycles: None,
+                call_depth: 0,
+            })
+            .unwrap();
+    }
+
+    let context = mock_context(
+        cycles_limit,
+        caller,
+        Hash::digest(Bytes::from("stats")),
+        nonce,
+    );
+    let stats = service.get_riscv_stats(context).unwrap();
+    assert_eq!(stats.contract_count, 2);
+    assert_eq!(stats.total_code_bytes, (binary.len() + js.len()) as u64);
+}
+
+#[test]
+fn test_deploy_with_salt() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let deploy_payload = DeployWithSaltPayload {
+        code:       hex::encode(binary),
+        intp_type:  InterpreterType::Binary,
+        init_args:  "".into(),
+        max_cycles: None,
+        salt:       Hash::digest(Bytes::from("salt")),
+        call_depth: 0,
+    };
+
+    let deploy_result = service
+        .deploy_with_salt(context.clone(), deploy_payload.clone())
+        .unwrap();
+
+    // Same caller, salt and code always derive same address
+    assert!(service
+        .deploy_with_salt(context.clone(), deploy_payload.clone())
+        .is_err());
+
+    let other_salt = DeployWithSaltPayload {
+        salt: Hash::digest(Bytes::from("other salt")),
+        ..deploy_payload
+    };
+    let other_result = service.deploy_with_salt(context, other_salt).unwrap();
+    assert_ne!(other_result.address, deploy_result.address);
+}
+
+#[test]
+fn test_deploy_code_too_large() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    service.conf.max_code_size = binary.len() - 1;
+
+    let deploy_payload = DeployPayload {
+        code:       hex::encode(&binary),
+        intp_type:  InterpreterType::Binary,
+        init_args:  "".into(),
+        max_cycles: None,
+        call_depth: 0,
+    };
+    let err = service
+        .deploy(context.clone(), deploy_payload.clone())
+        .unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("exceeds limit"));
+
+    let stats = service.get_riscv_stats(context.clone()).unwrap();
+    assert_eq!(stats.total_code_bytes, 0);
+
+    service.conf.max_code_size = binary.len();
+    assert!(service.deploy(context, deploy_payload).is_ok());
+}
+
+#[test]
+fn test_contract_max_cycles() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce.clone());
+
+    let mut service = new_riscv_service();
+
+    let binary = std::fs::read("src/tests/simple_storage").unwrap();
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(&binary),
+            intp_type:  InterpreterType::Binary,
+            init_args:  "".into(),
+            max_cycles: Some(100),
+            call_depth: 0,
+        })
+        .unwrap();
+
+    // Contract cap is hit long before tx cycles limit
+    let err = service
+        .exec(context.clone(), ExecPayload {
+            address:    deploy_result.address,
+            args:       "set k v".into(),
+            call_depth: 0,
+        })
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .to_lowercase()
+        .contains("contract out of cycles"));
+
+    let tx_hash = Hash::digest(Bytes::from("deploy without tight cap"));
+    let context = mock_context(cycles_limit, context.get_caller(), tx_hash, nonce);
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(&binary),
+            intp_type:  InterpreterType::Binary,
+            init_args:  "".into(),
+            max_cycles: Some(cycles_limit),
+            call_depth: 0,
+        })
+        .unwrap();
+    let exec_result = service.exec(context, ExecPayload {
+        address:    deploy_result.address,
+        args:       "set k v".into(),
+        call_depth: 0,
+    });
+    assert!(exec_result.is_ok());
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn test_deploy_and_run_wasm() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    // Empty module, header only
+    let module = b"\0asm\x01\0\0\0";
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(&module[..]),
+            intp_type:  InterpreterType::Wasm,
+            init_args:  "init".into(),
+            max_cycles: None,
+            call_depth: 0,
+        })
+        .unwrap();
+    assert_eq!(&deploy_result.init_ret, "");
+
+    let exec_result = service.exec(context.clone(), ExecPayload {
+        address:    deploy_result.address,
+        args:       "".into(),
+        call_depth: 0,
+    });
+    assert_eq!(&exec_result.unwrap(), "");
+
+    let deploy_result = service.deploy(context, DeployPayload {
+        code:       hex::encode("not a wasm module"),
+        intp_type:  InterpreterType::Wasm,
+        init_args:  "init".into(),
+        max_cycles: None,
+        call_depth: 0,
+    });
+    assert!(deploy_result.is_err());
+}
+
+#[cfg(not(feature = "duktape"))]
+#[test]
+fn test_deploy_duktape_disabled() {
+    let cycles_limit = 0x99_9999;
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(cycles_limit, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+
+    let err = service
+        .deploy(context, DeployPayload {
+            code:       hex::encode("function main() { return 'hello'; }"),
+            intp_type:  InterpreterType::Duktape,
+            init_args:  "".into(),
+            max_cycles: None,
+            call_depth: 0,
+        })
+        .unwrap_err();
+    assert!(err.to_string().to_lowercase().contains("not supported"));
+}
+
 struct MockDispatcher;
 
 impl Dispatcher for MockDispatcher {
@@ -106,16 +524,23 @@ impl Dispatcher for MockDispatcher {
     }
 
     fn write(&self, context: ServiceContext) -> ProtocolResult<ExecResp> {
-        let payload: ExecPayload =
-            serde_json::from_str(context.get_payload()).expect("dispatcher payload");
-
-        RISCV_SERVICE.with(|cell| {
-            let mut service = cell.borrow_mut();
-
-            Ok(ExecResp {
-                ret:      service.exec(context.clone(), payload)?,
-                is_error: false,
-            })
+        let mut service =
+            DISPATCHER_STATE.with(|state| new_riscv_service_with_state(Rc::clone(state)));
+
+        let ret = if context.get_service_method() == "deploy" {
+            let payload: DeployPayload =
+                serde_json::from_str(context.get_payload()).expect("dispatcher payload");
+            let resp = service.deploy(context, payload)?;
+            serde_json::to_string(&resp).expect("dispatcher ret")
+        } else {
+            let payload: ExecPayload =
+                serde_json::from_str(context.get_payload()).expect("dispatcher payload");
+            service.exec(context, payload)?
+        };
+
+        Ok(ExecResp {
+            ret,
+            is_error: false,
         })
     }
 }
@@ -127,19 +552,22 @@ fn new_riscv_service() -> RiscvService<
         MockDispatcher,
     >,
 > {
+    new_riscv_service_with_state(new_state())
+}
+
+fn new_riscv_service_with_state(state: TestState) -> TestRiscvService {
     let chain_db = DefaultChainQuerier::new(Arc::new(MockStorage {}));
-    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
-    let state = GeneralServiceState::new(trie);
 
-    let sdk = DefalutServiceSDK::new(
-        Rc::new(RefCell::new(state)),
-        Rc::new(chain_db),
-        MockDispatcher {},
-    );
+    let sdk = DefalutServiceSDK::new(state, Rc::new(chain_db), MockDispatcher {});
 
     RiscvService::init(sdk).unwrap()
 }
 
+fn new_state() -> TestState {
+    let trie = MPTTrie::new(Arc::new(MemoryDB::new(false)));
+    Rc::new(RefCell::new(GeneralServiceState::new(trie)))
+}
+
 fn mock_context(cycles_limit: u64, caller: Address, tx_hash: Hash, nonce: Hash) -> ServiceContext {
     let params = ServiceContextParams {
         tx_hash: Some(tx_hash),
@@ -0,0 +1,159 @@
+use ckb_vm::{
+    registers, CoreMachine, DefaultCoreMachine, Memory, SparseMemory, SupportMachine, Syscalls,
+};
+
+use protocol::types::{Address, Hash};
+
+use super::{mock_context, new_riscv_service};
+use crate::types::{DeployPayload, ExecPayload, InterpreterType};
+use crate::vm::SyscallLog;
+
+// Keep in sync with pvm.h
+const SYSCODE_LOG: u64 = 2004;
+const PVM_LOG_WARN: u64 = 1;
+
+const MSG_ADDR: u64 = 0x1000;
+const CONTRACT: &str = "0x0000000000000000000000000000000000000002";
+const LOG_MSG: &str = "balance is running low";
+
+type TestMachine = DefaultCoreMachine<u64, SparseMemory<u64>>;
+
+fn prepare_ecall(code: u64, args: &[u64]) -> TestMachine {
+    let mut machine = TestMachine::new_with_max_cycles(1024 * 1024);
+    machine.set_register(registers::A7, code);
+    for (idx, arg) in args.iter().enumerate() {
+        machine.set_register(registers::A0 + idx, *arg);
+    }
+
+    machine
+}
+
+fn store_str(machine: &mut TestMachine, addr: u64, s: &str) {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.push(0);
+
+    machine
+        .memory_mut()
+        .store_bytes(addr, &bytes)
+        .expect("store str");
+}
+
+#[test]
+fn should_support_pvm_log() {
+    let address = Address::from_hex(CONTRACT).expect("contract address");
+    let mut syscall = SyscallLog::new(address);
+
+    let msg = LOG_MSG;
+    let mut machine = prepare_ecall(SYSCODE_LOG, &[PVM_LOG_WARN, MSG_ADDR]);
+    store_str(&mut machine, MSG_ADDR, msg);
+
+    assert!(syscall.ecall(&mut machine).expect("log warn"));
+    assert_eq!(machine.registers()[registers::A0], 0);
+    // Cycles are charged by message length
+    assert_eq!(machine.cycles(), msg.len() as u64 * 10);
+
+    // Unknown level doesn't abort execution, but reports failure
+    let mut machine = prepare_ecall(SYSCODE_LOG, &[99, MSG_ADDR]);
+    store_str(&mut machine, MSG_ADDR, msg);
+
+    assert!(syscall.ecall(&mut machine).expect("log unknown level"));
+    assert_eq!(machine.registers()[registers::A0], 1);
+}
+
+#[test]
+fn should_run_contract_with_pvm_log() {
+    let caller = Address::from_hex("0x755cdba6ae4f479f7164792b318b2a06c759833b").unwrap();
+    let tx_hash =
+        Hash::from_hex("412a6c54cf3d3dbb16b49c34e6cd93d08a245298032eb975ee51105b4c296828").unwrap();
+    let nonce =
+        Hash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    let context = mock_context(0x99_9999, caller, tx_hash, nonce);
+
+    let mut service = new_riscv_service();
+    let deploy_result = service
+        .deploy(context.clone(), DeployPayload {
+            code:       hex::encode(log_contract()),
+            intp_type:  InterpreterType::Binary,
+            init_args:  "".into(),
+            max_cycles: None,
+            call_depth: 0,
+        })
+        .expect("deploy log contract");
+
+    // Logging at warn level doesn't stop the contract, it goes on to emit
+    // the event and exits with 0
+    let ret = service
+        .exec(
+            context.clone(),
+            ExecPayload::new(deploy_result.address, "".into()),
+        )
+        .expect("exec log contract");
+    assert_eq!(ret, "");
+
+    let events = context.get_events();
+    assert!(events.iter().any(|ev| ev.data == LOG_MSG));
+}
+
+fn log_contract() -> Vec<u8> {
+    const BASE: u64 = 0x1_0000;
+    const CODE_OFFSET: u64 = 0x78;
+    const MSG_OFFSET: u64 = 0x100;
+
+    // Hand assembled so that no riscv toolchain is required, roughly
+    //   int ret = pvm_log(PVM_LOG_WARN, msg);
+    //   if (ret) return ret;
+    //   pvm_emit_event(msg, strlen(msg));
+    //   return 0;
+    let code: [u32; 15] = [
+        0x0010_0513, // addi a0, zero, 1
+        0x0001_05b7, // lui a1, 0x10
+        0x1005_8593, // addi a1, a1, 0x100
+        0x7d40_0893, // addi a7, zero, 2004
+        0x0000_0073, // ecall
+        0x0205_1063, // bne a0, zero, 32
+        0x0001_0537, // lui a0, 0x10
+        0x1005_0513, // addi a0, a0, 0x100
+        0x0160_0593, // addi a1, zero, 22
+        0x0000_18b7, // lui a7, 1
+        0xbc28_8893, // addi a7, a7, -1086
+        0x0000_0073, // ecall
+        0x0000_0513, // addi a0, zero, 0
+        0x05d0_0893, // addi a7, zero, 93
+        0x0000_0073, // ecall
+    ];
+
+    // Message length is loaded as an immediate above
+    assert_eq!(LOG_MSG.len(), 22);
+
+    let mut body = vec![0u8; MSG_OFFSET as usize];
+    for (idx, inst) in code.iter().enumerate() {
+        let start = CODE_OFFSET as usize + idx * 4;
+        body[start..start + 4].copy_from_slice(&inst.to_le_bytes());
+    }
+    body.extend_from_slice(LOG_MSG.as_bytes());
+    body.push(0);
+    let size = body.len() as u64;
+
+    let mut elf = Vec::new();
+    // ELF header, 64 bit little endian RISC-V executable
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    elf.extend_from_slice(&2u16.to_le_bytes());
+    elf.extend_from_slice(&0xf3u16.to_le_bytes());
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&(BASE + CODE_OFFSET).to_le_bytes());
+    elf.extend_from_slice(&64u64.to_le_bytes());
+    elf.extend_from_slice(&0u64.to_le_bytes());
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for half in &[64u16, 56, 1, 64, 0, 0] {
+        elf.extend_from_slice(&half.to_le_bytes());
+    }
+    // Single read and execute segment mapping the whole file
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&5u32.to_le_bytes());
+    for word in &[0, BASE, BASE, size, size, 0x1000u64] {
+        elf.extend_from_slice(&word.to_le_bytes());
+    }
+
+    elf.extend_from_slice(&body[elf.len()..]);
+    elf
+}
@@ -1,5 +1,8 @@
 function _test_init() {
   const args = PVM.load_args();
+  if (args.indexOf('test_recursive_deploy') != -1) {
+    return _test_recursive_deploy();
+  }
   return args;
 }
 
@@ -112,6 +115,33 @@ function _test_service_call() {
   );
 }
 
+function _test_recursive_call() {
+  return PVM.contract_call(
+    PVM.address(),
+    JSON.stringify({ method: 'test_recursive_call' })
+  );
+}
+
+function _test_recursive_deploy() {
+  const args = PVM.load_json_args();
+  return PVM.service_call(
+    'riscv',
+    'deploy',
+    JSON.stringify({
+      code: args.code,
+      intp_type: 'Duktape',
+      init_args: PVM.load_args()
+    })
+  );
+}
+
+function _test_busy_loop() {
+  var i = 0;
+  for (;;) {
+    i += 1;
+  }
+}
+
 function main() {
   'use strict';
 
@@ -161,6 +191,12 @@ function main() {
     return _test_contract_call();
   } else if (args.method == 'test_service_call') {
     return _test_service_call();
+  } else if (args.method == 'test_recursive_call') {
+    return _test_recursive_call();
+  } else if (args.method == 'test_recursive_deploy') {
+    return _test_recursive_deploy();
+  } else if (args.method == 'test_busy_loop') {
+    return _test_busy_loop();
   } else if (args.method == '_ret_caller_and_origin') {
     return _ret_caller_and_origin();
   } else if (args.method == '_ret_self') {
@@ -13,8 +13,18 @@ use std::convert::TryFrom;
 #[derive(Deserialize, Serialize, Clone, Debug, Copy)]
 pub enum InterpreterType {
     Binary = 1,
-    #[cfg(debug_assertions)]
     Duktape = 2,
+    #[cfg(feature = "wasm")]
+    Wasm = 3,
+}
+
+impl InterpreterType {
+    pub fn is_enabled(self) -> bool {
+        match self {
+            InterpreterType::Duktape => cfg!(feature = "duktape"),
+            _ => true,
+        }
+    }
 }
 
 impl TryFrom<u8> for InterpreterType {
@@ -23,18 +33,44 @@ impl TryFrom<u8> for InterpreterType {
     fn try_from(val: u8) -> Result<InterpreterType, Self::Error> {
         match val {
             1 => Ok(InterpreterType::Binary),
-            #[cfg(debug_assertions)]
             2 => Ok(InterpreterType::Duktape),
+            #[cfg(feature = "wasm")]
+            3 => Ok(InterpreterType::Wasm),
             _ => Err("unsupport interpreter"),
         }
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct InitGenesisPayload {
+    // Wall-clock limit of read-only calls in milliseconds, none by default
+    #[serde(default)]
+    pub call_timeout_ms: Option<u64>,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DeployPayload {
-    pub code:      String,
-    pub intp_type: InterpreterType,
-    pub init_args: String,
+    pub code:       String,
+    pub intp_type:  InterpreterType,
+    pub init_args:  String,
+    // Cycles cap of every call to this contract, bounded by tx cycles limit
+    #[serde(default)]
+    pub max_cycles: Option<u64>,
+    // Depth of the init call when deployed by a contract, set by the service
+    #[serde(default)]
+    pub call_depth: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DeployWithSaltPayload {
+    pub code:       String,
+    pub intp_type:  InterpreterType,
+    pub init_args:  String,
+    #[serde(default)]
+    pub max_cycles: Option<u64>,
+    pub salt:       Hash,
+    #[serde(default)]
+    pub call_depth: usize,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -43,10 +79,23 @@ pub struct DeployResp {
     pub init_ret: String,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Constructor)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct ExecPayload {
-    pub address: Address,
-    pub args:    String,
+    pub address:    Address,
+    pub args:       String,
+    // Number of contract calls leading to this one, set by the service
+    #[serde(default)]
+    pub call_depth: usize,
+}
+
+impl ExecPayload {
+    pub fn new(address: Address, args: String) -> Self {
+        ExecPayload {
+            address,
+            args,
+            call_depth: 0,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
@@ -55,6 +104,18 @@ pub struct ExecResp {
     pub is_error: bool,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct EstimateExecResp {
+    pub cycles_used: u64,
+    pub ret:         String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct RiscvStats {
+    pub contract_count:   u64,
+    pub total_code_bytes: u64,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub struct InterpreterResult {
     pub cycles_used: u64,
@@ -64,8 +125,9 @@ pub struct InterpreterResult {
 
 #[derive(Deserialize, Serialize, Clone, Debug, Constructor)]
 pub struct Contract {
-    pub code_hash: Hash,
-    pub intp_type: InterpreterType,
+    pub code_hash:  Hash,
+    pub intp_type:  InterpreterType,
+    pub max_cycles: Option<u64>,
 }
 
 impl FixedCodec for Contract {
@@ -80,9 +142,12 @@ impl FixedCodec for Contract {
 
 impl rlp::Encodable for Contract {
     fn rlp_append(&self, s: &mut rlp::RlpStream) {
-        s.begin_list(2)
+        let max_cycles: Vec<u64> = self.max_cycles.iter().cloned().collect();
+
+        s.begin_list(3)
             .append(&self.code_hash)
-            .append(&(self.intp_type as u8));
+            .append(&(self.intp_type as u8))
+            .append_list(&max_cycles);
     }
 }
 
@@ -90,10 +155,16 @@ impl rlp::Decodable for Contract {
     fn decode(r: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
         let code_hash: Hash = r.val_at(0)?;
         let intp_type: u8 = r.val_at(1)?;
+        let max_cycles = if r.item_count()? > 2 {
+            r.at(2)?.as_list::<u64>()?.pop()
+        } else {
+            None
+        };
 
         Ok(Contract {
             code_hash,
             intp_type: InterpreterType::try_from(intp_type).map_err(rlp::DecoderError::Custom)?,
+            max_cycles,
         })
     }
 }
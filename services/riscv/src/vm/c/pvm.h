@@ -29,6 +29,11 @@ static inline long __internal_syscall(long n, long _a0, long _a1, long _a2,
 #define SYSCODE_LOAD_ARGS 2001
 #define SYSCODE_RET 2002
 #define SYSCODE_ASSERT 2003
+#define SYSCODE_LOG 2004
+
+#define PVM_LOG_INFO 0
+#define PVM_LOG_WARN 1
+#define PVM_LOG_ERROR 2
 
 #define SYSCODE_CYCLE_LIMIT 3000
 #define SYSCODE_IS_INIT 3001
@@ -83,6 +88,24 @@ void pvm_assert(int statement, const char *msg) {
   syscall(SYSCODE_ASSERT, statement, msg, 0, 0, 0, 0);
 }
 
+/*
+ * Function pvm_log accepts a log level and a message that will be written to
+ * the node log. Unlike pvm_debug, it is also available in release builds.
+ *
+ * Params:
+ *   level[in]: PVM_LOG_INFO, PVM_LOG_WARN or PVM_LOG_ERROR
+ *   msg[in]: message to log
+ *
+ * Return:
+ *   code: 0(success), 1(unknown level)
+ *
+ * Example:
+ *   pvm_log(PVM_LOG_WARN, "balance is running low");
+ */
+int pvm_log(int level, const char *msg) {
+  return syscall(SYSCODE_LOG, level, msg, 0, 0, 0, 0);
+}
+
 /*
  * Function pvm_load_args load contract invocation arguments.
  *
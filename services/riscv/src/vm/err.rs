@@ -1,10 +1,17 @@
 use std::error;
 use std::fmt;
 
+use crate::types::InterpreterType;
+
 #[derive(Debug)]
 pub enum Error {
     VM(ckb_vm::Error),
     ExitCodeError,
+    Timeout,
+    ContractOutOfCycles(u64),
+    UnsupportedInterpreter(InterpreterType),
+    #[cfg(feature = "wasm")]
+    InvalidWasmModule,
 }
 
 impl error::Error for Error {}
@@ -13,6 +20,13 @@ impl fmt::Display for Error {
         match self {
             Error::VM(e) => return write!(f, "{:?}", e),
             Error::ExitCodeError => return write!(f, "ExitCodeError"),
+            Error::Timeout => return write!(f, "Timeout"),
+            Error::ContractOutOfCycles(cap) => return write!(f, "ContractOutOfCycles({})", cap),
+            Error::UnsupportedInterpreter(t) => {
+                return write!(f, "UnsupportedInterpreter({:?})", t)
+            }
+            #[cfg(feature = "wasm")]
+            Error::InvalidWasmModule => return write!(f, "InvalidWasmModule"),
         };
     }
 }
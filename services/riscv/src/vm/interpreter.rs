@@ -1,9 +1,12 @@
 use std::cell::RefCell;
 use std::io;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
-use ckb_vm::{DefaultMachineBuilder, SupportMachine};
+use ckb_vm::{DefaultMachineBuilder, Instruction, SupportMachine};
 
 use protocol::{
     types::{Address, ServiceContext},
@@ -15,9 +18,14 @@ use crate::vm;
 use crate::vm::ChainInterface;
 
 // Duktape execution environment
-#[cfg(debug_assertions)]
+#[cfg(feature = "duktape")]
 const DUKTAPE_EE: &[u8] = std::include_bytes!("c/duktape_ee.bin");
 
+// Default upper bound of deployed code, 1 MB
+pub const MAX_CODE_SIZE: usize = 1024 * 1024;
+// Default upper bound of nested contract calls
+pub const MAX_CALL_DEPTH: usize = 8;
+
 #[derive(Clone, Debug)]
 pub enum MachineType {
     NativeRust,
@@ -26,25 +34,35 @@ pub enum MachineType {
 
 #[derive(Clone, Debug)]
 pub struct InterpreterConf {
-    pub print_debug:  bool,
-    pub machine_type: MachineType,
+    pub print_debug:    bool,
+    pub machine_type:   MachineType,
+    // Wall-clock limit, independent of cycles. Not deterministic across
+    // nodes, only set for read-only calls
+    pub timeout:        Option<Duration>,
+    pub max_code_size:  usize,
+    pub max_call_depth: usize,
 }
 
 impl Default for InterpreterConf {
     fn default() -> Self {
         InterpreterConf {
-            print_debug:  true,
-            machine_type: MachineType::Asm,
+            print_debug:    true,
+            machine_type:   MachineType::Asm,
+            timeout:        None,
+            max_code_size:  MAX_CODE_SIZE,
+            max_call_depth: MAX_CALL_DEPTH,
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct InterpreterParams {
-    pub address: Address,
-    pub code:    Bytes,
-    pub args:    Bytes,
-    pub is_init: bool,
+    pub address:    Address,
+    pub code:       Bytes,
+    pub args:       Bytes,
+    pub is_init:    bool,
+    // Per contract cycles cap, tx cycles limit still applies
+    pub max_cycles: Option<u64>,
 }
 
 pub struct Interpreter {
@@ -72,7 +90,7 @@ impl Interpreter {
         }
     }
 
-    pub fn run(&mut self) -> Result<InterpreterResult, ckb_vm::Error> {
+    pub fn run(&mut self) -> Result<InterpreterResult, vm::Error> {
         let (debug_output, assert_output) = if self.cfg.print_debug {
             (
                 Box::new(io::stdout()) as Box<dyn io::Write>,
@@ -87,8 +105,14 @@ impl Interpreter {
 
         let (code, init_payload) = match self.r#type {
             InterpreterType::Binary => (self.iparams.code.clone(), None),
-            #[cfg(debug_assertions)]
+            #[cfg(feature = "duktape")]
             InterpreterType::Duktape => (Bytes::from(DUKTAPE_EE), Some(self.iparams.code.clone())),
+            #[cfg(not(feature = "duktape"))]
+            InterpreterType::Duktape => {
+                return Err(vm::Error::UnsupportedInterpreter(InterpreterType::Duktape))
+            }
+            #[cfg(feature = "wasm")]
+            InterpreterType::Wasm => return vm::wasm::run(&self.iparams.code),
         };
 
         let mut args: Vec<Bytes> = vec!["main".into()];
@@ -97,9 +121,36 @@ impl Interpreter {
         }
 
         let ret_data = Rc::new(RefCell::new(Vec::new()));
-        let cycles_lmit = self.context.get_cycles_limit();
-        let (exitcode, cycles) = match self.cfg.machine_type {
+        // VM cycles count from the start of this run, nested calls included
+        let cycles_remain = self
+            .context
+            .get_cycles_limit()
+            .saturating_sub(self.context.get_cycles_used());
+        let capped_by_contract = self
+            .iparams
+            .max_cycles
+            .map_or(false, |cap| cap < cycles_remain);
+        let cycles_lmit = match self.iparams.max_cycles {
+            Some(cap) => cap.min(cycles_remain),
+            None => cycles_remain,
+        };
+        let deadline = self.cfg.timeout.map(|timeout| Instant::now() + timeout);
+        let timed_out = Arc::new(AtomicBool::new(false));
+        // Asm machine caches instruction cycles per trace, only native machine
+        // charges every executed instruction, where the deadline is checked.
+        let machine_type = if deadline.is_some() {
+            MachineType::NativeRust
+        } else {
+            self.cfg.machine_type.clone()
+        };
+        let (exitcode, cycles) = match machine_type {
             MachineType::NativeRust => {
+                let cycle_func: Box<dyn Fn(Instruction) -> u64 + Send + Sync> = match deadline {
+                    Some(deadline) => {
+                        vm::deadline_instruction_cycles(deadline, Arc::clone(&timed_out))
+                    }
+                    None => Box::new(vm::cost_model::instruction_cycles),
+                };
                 let core_machine =
                     ckb_vm::DefaultCoreMachine::<u64, ckb_vm::SparseMemory<u64>>::new_with_max_cycles(
                         cycles_lmit
@@ -107,7 +158,11 @@ impl Interpreter {
                 let mut machine = ckb_vm::DefaultMachineBuilder::<
                     ckb_vm::DefaultCoreMachine<u64, ckb_vm::SparseMemory<u64>>,
                 >::new(core_machine)
-                .instruction_cycle_func(Box::new(vm::cost_model::instruction_cycles))
+                .instruction_cycle_func(cycle_func)
+                .syscall(Box::new(vm::SyscallDeadline::new(
+                    deadline,
+                    Arc::clone(&timed_out),
+                )))
                 .syscall(Box::new(vm::SyscallDebug::new(
                     "[ckb-vm debug]",
                     debug_output,
@@ -116,6 +171,7 @@ impl Interpreter {
                     "[ckb-vm assert]",
                     assert_output,
                 )))
+                .syscall(Box::new(vm::SyscallLog::new(self.iparams.address.clone())))
                 .syscall(Box::new(vm::SyscallEnvironment::new(
                     self.context.clone(),
                     self.iparams.clone(),
@@ -129,7 +185,9 @@ impl Interpreter {
                 )))
                 .build();
                 machine.load_program(&code, &args[..]).unwrap();
-                let exitcode = machine.run()?;
+                let exitcode = machine
+                    .run()
+                    .map_err(|e| self.vm_error(&timed_out, capped_by_contract, e))?;
                 let cycles = machine.cycles();
                 (exitcode, cycles)
             }
@@ -145,6 +203,7 @@ impl Interpreter {
                         "[ckb-vm assert]",
                         assert_output,
                     )))
+                    .syscall(Box::new(vm::SyscallLog::new(self.iparams.address.clone())))
                     .syscall(Box::new(vm::SyscallEnvironment::new(
                         self.context.clone(),
                         self.iparams.clone(),
@@ -159,7 +218,9 @@ impl Interpreter {
                     .build();
                 let mut machine = AsmMachine::new(machine, None);
                 machine.load_program(&code, &args[..]).unwrap();
-                let exitcode = machine.run()?;
+                let exitcode = machine
+                    .run()
+                    .map_err(|e| self.vm_error(&timed_out, capped_by_contract, e))?;
                 let cycles = machine.machine.cycles();
                 (exitcode, cycles)
             }
@@ -172,4 +233,22 @@ impl Interpreter {
         };
         Ok(result)
     }
+
+    fn vm_error(
+        &self,
+        timed_out: &AtomicBool,
+        capped_by_contract: bool,
+        err: ckb_vm::Error,
+    ) -> vm::Error {
+        if timed_out.load(Ordering::SeqCst) {
+            return vm::Error::Timeout;
+        }
+
+        match (err, self.iparams.max_cycles) {
+            (ckb_vm::Error::InvalidCycles, Some(cap)) if capped_by_contract => {
+                vm::Error::ContractOutOfCycles(cap)
+            }
+            (err, _) => vm::Error::VM(err),
+        }
+    }
 }
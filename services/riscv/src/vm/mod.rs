@@ -5,12 +5,18 @@ mod err;
 pub use err::Error;
 
 mod interpreter;
-pub use interpreter::{Interpreter, InterpreterConf, InterpreterParams};
+pub use interpreter::{
+    Interpreter, InterpreterConf, InterpreterParams, MAX_CALL_DEPTH, MAX_CODE_SIZE,
+};
 
 mod syscall;
 pub use syscall::{
-    SyscallAssert, SyscallChainInterface, SyscallDebug, SyscallEnvironment, SyscallIO,
+    deadline_instruction_cycles, SyscallAssert, SyscallChainInterface, SyscallDeadline,
+    SyscallDebug, SyscallEnvironment, SyscallIO, SyscallLog,
 };
 
+#[cfg(feature = "wasm")]
+mod wasm;
+
 mod chain_interface;
 pub use chain_interface::ChainInterface;
@@ -2,6 +2,7 @@ pub const SYSCODE_DEBUG: u64 = 2000;
 pub const SYSCODE_LOAD_ARGS: u64 = 2001;
 pub const SYSCODE_RET: u64 = 2002;
 pub const SYSCODE_ASSERT: u64 = 2003;
+pub const SYSCODE_LOG: u64 = 2004;
 
 pub const SYSCODE_CYCLE_LIMIT: u64 = 3000;
 pub const SYSCODE_IS_INIT: u64 = 3001;
@@ -21,3 +22,7 @@ pub const SYSCODE_GET_STORAGE: u64 = 4000;
 pub const SYSCODE_SET_STORAGE: u64 = 4001;
 pub const SYSCODE_CONTRACT_CALL: u64 = 4002;
 pub const SYSCODE_SERVICE_CALL: u64 = 4003;
+
+pub const LOG_LEVEL_INFO: u64 = 0;
+pub const LOG_LEVEL_WARN: u64 = 1;
+pub const LOG_LEVEL_ERROR: u64 = 2;
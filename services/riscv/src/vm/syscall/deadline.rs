@@ -0,0 +1,68 @@
+//! Aborts the execution once a wall-clock deadline has passed.
+//!
+//! Wall-clock time is not deterministic across nodes, so the service only sets
+//! a deadline for read-only calls, never for transactions. The deadline is
+//! checked whenever the contract traps into the host, and periodically while
+//! instructions are charged, so pure compute loops are interrupted as well.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use ckb_vm::Instruction;
+
+use crate::vm::cost_model::instruction_cycles;
+
+// Instructions executed between two deadline checks
+const CHECK_INTERVAL: u64 = 4096;
+
+pub struct SyscallDeadline {
+    deadline:  Option<Instant>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl SyscallDeadline {
+    pub fn new(deadline: Option<Instant>, timed_out: Arc<AtomicBool>) -> Self {
+        Self {
+            deadline,
+            timed_out,
+        }
+    }
+}
+
+impl<Mac: ckb_vm::SupportMachine> ckb_vm::Syscalls<Mac> for SyscallDeadline {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), ckb_vm::Error> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, _machine: &mut Mac) -> Result<bool, ckb_vm::Error> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.timed_out.store(true, Ordering::SeqCst);
+                Err(ckb_vm::Error::Unexpected)
+            }
+            // Let the following syscalls handle this ecall
+            _ => Ok(false),
+        }
+    }
+}
+
+// Wraps the cost model, once the deadline has passed every instruction costs
+// u64::MAX cycles, which stops the machine with a cycles error.
+pub fn deadline_instruction_cycles(
+    deadline: Instant,
+    timed_out: Arc<AtomicBool>,
+) -> Box<dyn Fn(Instruction) -> u64 + Send + Sync> {
+    let executed = AtomicU64::new(0);
+
+    Box::new(move |i: Instruction| {
+        let count = executed.fetch_add(1, Ordering::Relaxed);
+        if timed_out.load(Ordering::Relaxed)
+            || (count % CHECK_INTERVAL == 0 && Instant::now() >= deadline)
+        {
+            timed_out.store(true, Ordering::SeqCst);
+            return u64::max_value();
+        }
+
+        instruction_cycles(i)
+    })
+}
@@ -0,0 +1,58 @@
+//! Provides leveled logging, let the contract write information to the node
+//! log in both debug and release builds.
+use ckb_vm::instructions::Register;
+use log::{error, info, warn};
+
+use protocol::types::Address;
+
+use crate::vm::syscall::common::get_str;
+use crate::vm::syscall::convention::{
+    LOG_LEVEL_ERROR, LOG_LEVEL_INFO, LOG_LEVEL_WARN, SYSCODE_LOG,
+};
+
+pub struct SyscallLog {
+    address: Address,
+}
+
+impl SyscallLog {
+    pub fn new(address: Address) -> Self {
+        Self { address }
+    }
+}
+
+impl<Mac: ckb_vm::SupportMachine> ckb_vm::Syscalls<Mac> for SyscallLog {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), ckb_vm::Error> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, ckb_vm::Error> {
+        let code = &machine.registers()[ckb_vm::registers::A7];
+        if code.to_u64() != SYSCODE_LOG {
+            return Ok(false);
+        }
+
+        let level = machine.registers()[ckb_vm::registers::A0].to_u64();
+        let msg_addr = machine.registers()[ckb_vm::registers::A1].to_u64();
+        // Cycles are charged by message length in get_str
+        let msg = get_str(machine, msg_addr)?;
+
+        let ret = match level {
+            LOG_LEVEL_INFO => {
+                info!("[contract {}] {}", self.address.as_hex(), msg);
+                0
+            }
+            LOG_LEVEL_WARN => {
+                warn!("[contract {}] {}", self.address.as_hex(), msg);
+                0
+            }
+            LOG_LEVEL_ERROR => {
+                error!("[contract {}] {}", self.address.as_hex(), msg);
+                0
+            }
+            _ => 1,
+        };
+
+        machine.set_register(ckb_vm::registers::A0, Mac::REG::from_u8(ret));
+        Ok(true)
+    }
+}
@@ -2,12 +2,18 @@ mod common;
 
 mod convention;
 
+mod deadline;
+pub use deadline::{deadline_instruction_cycles, SyscallDeadline};
+
 mod debug;
 pub use debug::SyscallDebug;
 
 mod assert;
 pub use assert::SyscallAssert;
 
+mod log;
+pub use self::log::SyscallLog;
+
 mod environment;
 pub use environment::SyscallEnvironment;
 
@@ -0,0 +1,28 @@
+//! Stub WASM execution environment.
+//!
+//! There is no bundled WASM engine yet. The stub only checks the module
+//! header, then runs it as a module without start function or exports, so
+//! deploy and exec keep the same flow as other interpreter types.
+use protocol::Bytes;
+
+use crate::types::InterpreterResult;
+use crate::vm;
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+const WASM_VERSION: &[u8] = &[1, 0, 0, 0];
+const WASM_HEADER_LEN: usize = 8;
+
+pub fn run(code: &Bytes) -> Result<InterpreterResult, vm::Error> {
+    if code.len() < WASM_HEADER_LEN
+        || &code[0..4] != WASM_MAGIC
+        || &code[4..WASM_HEADER_LEN] != WASM_VERSION
+    {
+        return Err(vm::Error::InvalidWasmModule);
+    }
+
+    Ok(InterpreterResult {
+        cycles_used: code.len() as u64,
+        ret:         Bytes::new(),
+        ret_code:    0,
+    })
+}